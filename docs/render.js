@@ -4,37 +4,38 @@ import * as rust from './rust_web_roguelike.js';
 //JS Lisp implementation
 import {res} from './mal.js';
 
-var term, eng, inventoryOverlay, vendorOverlay, viewOverlay,logOverlay; // Can't be initialized yet because DOM is not ready
+var term, eng, inventoryOverlay, vendorOverlay, viewOverlay,logOverlay,slotsOverlay; // Can't be initialized yet because DOM is not ready
 var universe, g_wasm, map, player, entities_mem,w,h; // Can't be initialized yet because WASM is not ready
+var level = 0; //the map tiles only change when the player takes the stairs
 var mouse = null
 var automoving = false;
 
 // The tile palette is precomputed in order to not have to create
 // thousands of Tiles on the fly.
 var AT = new ut.Tile("@", 255, 255, 255);
-var THUG = new ut.Tile("t", 55, 0, 0); //, 255, 0, 0); //red bg means hostile
-var KNIFE = new ut.Tile("/", 0, 255, 255);
-var MED = new ut.Tile("!", 255, 0, 0);
-var BARKEEP = new ut.Tile("☺", 0, 128, 255); //, 255, 255, 0); //yellow bg means neutral
-var PATRON = new ut.Tile("☺", 100, 100, 100); //, 255, 255, 0);
-
-var BOOTS = new ut.Tile("]", 129, 77, 4, 255,255,255);
-var JACKET = new ut.Tile("]", 255,124,0, 255,255,255);
-var JEANS = new ut.Tile("]", 0, 23, 255, 255,255,255);
-
-
-var TABLE = new ut.Tile("╦", 170, 170, 170);
-var CHAIR = new ut.Tile("└", 170, 170, 170);
-var BED = new ut.Tile("#", 0, 128, 128);
-
-var WALL = new ut.Tile('▒', 100, 100, 100);
-var FLOOR = new ut.Tile('.', 50, 50, 50);
-var GRASS = new ut.Tile(',', 0, 255, 0);
-var TREE = new ut.Tile('♣', 0, 153, 0);
-var FLOOR_INDOOR = new ut.Tile('.', 0, 128, 128);
-var DOOR = new ut.Tile("+", 211, 211, 211);
-var WATER = new ut.Tile("~", 0, 0, 255);
-var MOUNTAIN = new ut.Tile("▓", 200, 200, 200); //▲ fits some sort of rubble and # is more fitting for a web imho
+
+//map tiles and entity glyphs come from the data file
+//TILES is indexed by the tile id get_tiles() gives, GLYPHS by the glyph id draw_entities() gives
+var TILES = [];
+var GLYPHS = [];
+
+function defTile(d) {
+    return d.bg ? new ut.Tile(d.glyph, d.fg[0], d.fg[1], d.fg[2], d.bg[0], d.bg[1], d.bg[2])
+                : new ut.Tile(d.glyph, d.fg[0], d.fg[1], d.fg[2]);
+}
+
+function loadTilePalette() {
+    TILES = [];
+    var defs = JSON.parse(universe.tile_defs());
+    for (var i = 0; i < defs.length; i++) {
+        TILES[defs[i].id] = defTile(defs[i]);
+    }
+    GLYPHS = [];
+    defs = JSON.parse(universe.glyph_defs());
+    for (var i = 0; i < defs.length; i++) {
+        GLYPHS[defs[i].id] = defTile(defs[i]);
+    }
+}
 
 //JS stub logic starts here
 
@@ -75,17 +76,7 @@ function getDungeonTile(x, y) {
     catch(err) { return ut.NULLTILE; }
 
     //map rust values to our tiles
-    if (v == 0 ) { return FLOOR };
-    if (v == 1 ) { return WALL };
-    if (v == 2 ) { return GRASS };
-    if (v == 3 ) { return TREE };
-    if (v == 4 ) { return FLOOR_INDOOR};
-    if (v == 5 ) { return DOOR};
-    if (v == 6 ) { return MOUNTAIN};
-    if (v == 7 ) { return WATER};
-  	
-	if (t === '#') return WALL;
-	if (t === '.') return FLOOR;
+    if (TILES[v] !== undefined) { return TILES[v]; }
 	return ut.NULLTILE;
 }
 
@@ -112,6 +103,12 @@ function getRenderTile(x,y) {
 // Main loop
 function tick() {
     var i, len, ex, ey, tile, tilex, tiley; //cache
+    //debug console "reload"
+    if (universe.apply_reload()) { loadTilePalette(); }
+    if (universe.level() != level) {
+        level = universe.level();
+        map = universe.get_tiles();
+    }
     player = universe.player();
 
     //player is always centered (see below); cx is half width
@@ -134,32 +131,19 @@ function tick() {
 		tilex = ex - cam_x;
         tiley = ey - cam_y;
         //substitute correct glyph
-        if (tile == 0) { tile = THUG; }
-        if (tile == 1) { tile = KNIFE; }
-        if (tile == 2) { tile = MED; }
-        if (tile == 3) { tile = BARKEEP; }
-        if (tile == 4) { tile = TABLE; }
-        if (tile == 5) { tile = CHAIR; }
-        if (tile == 6 ) { tile = BOOTS};
-        if (tile == 7 ) { tile = JACKET};
-        if (tile == 8 ) { tile = JEANS};
-        if (tile == 9) { tile = PATRON};
-        if (tile == 10) { tile = BED};
-
-		// if (e.tile == null || e.tile == undefined) {
-		// 	console.log("Tile for " + e + " is null!");
-		// 	continue;
-        // }
+        tile = GLYPHS[tile];
+		if (tile === undefined) {
+			console.log("No glyph for " + entities_mem[i + 2]);
+			continue;
+        }
         
-        //mark attitude/faction with background color
-        if (tile == THUG || tile == BARKEEP || tile == PATRON) {
-           var fact = universe.get_faction(ex, ey);
-           if (fact == 0) {    
-               term.put(new ut.Tile(tile.ch, tile.r, tile.g, tile.b, 255, 0, 0), tilex, tiley); //red bg means hostile
-           }
-           if (fact == 1) {
-               term.put(new ut.Tile(tile.ch, tile.r, tile.g, tile.b, 255, 255, 0), tilex, tiley); //yellow bg means neutral
-           }
+        //mark attitude/faction with background color (99 means no faction)
+        var fact = universe.get_faction(ex, ey);
+        if (fact == 0) {
+            term.put(new ut.Tile(tile.ch, tile.r, tile.g, tile.b, 255, 0, 0), tilex, tiley); //red bg means hostile
+        }
+        else if (fact == 1) {
+            term.put(new ut.Tile(tile.ch, tile.r, tile.g, tile.b, 255, 255, 0), tilex, tiley); //yellow bg means neutral
         }
         else {
             term.put(tile, tilex, tiley);            
@@ -306,6 +290,31 @@ function creationSelect(el) {
 
 }
 
+//the data file's scenarios, picked before the stats since they can change them
+function fillScenarios() {
+    var select = document.getElementById("scenario");
+    var list = JSON.parse(universe.scenarios());
+    select.innerHTML = "";
+    for (var i = 0; i < list.length; i++) {
+        var opt = document.createElement("option");
+        opt.text = list[i].name;
+        opt.title = list[i].description;
+        select.add(opt);
+        if (list[i].name == universe.scenario()) {
+            select.selectedIndex = i;
+            document.getElementById("scenario-desc").textContent = list[i].description;
+        }
+    }
+    select.onchange = function(e) { scenarioSelect(e.target); }
+}
+
+//the game was already set up from the old one, so start over with ?scenario= (seed and mods stay)
+function scenarioSelect(el) {
+    var params = new URLSearchParams(window.location.search);
+    params.set("scenario", el.options[el.selectedIndex].text);
+    window.location.search = params.toString();
+}
+
 function confirmCreation() {
     var allow = true;
     var lines = document.getElementById("creation").getElementsByTagName("li");
@@ -390,6 +399,74 @@ function createViewListOverlay() {
 }
 
 
+//saved games
+function createSlotsOverlay() {
+    const overlay = document.querySelector("#slots");
+    let visible = false;
+    let slots = [];
+
+    function draw() {
+        let html = `<div>SAVED GAMES</div><ul>`;
+        //newest first, so that after a crash the latest autosave is right at the top
+        slots = JSON.parse(universe.list_save_slots()).sort((a, b) => Date.parse(b.timestamp) - Date.parse(a.timestamp));
+        let len = slots.length;
+        for (var i = 0; i < len; ++i) {
+            var s = slots[i];
+            var saved = new Date(s.timestamp).toLocaleString();
+            html += `<li><button class="slot_button" id=slot-${i}>${String.fromCharCode(65 + i)}</button> ${s.name} (saved ${saved}): ${s.location}, ${s.time} (turn ${s.turns}), HP ${s.hp}/${s.max_hp}, $${s.money} <button class="drop_button" id=slot-del-${i}>d</button></li>`;
+        }
+        html += `</ul>`;
+        if (len == 0) {
+            html = `<div>No saved games yet, <kbd>S</kbd> saves (and the game autosaves as you play). <kbd>Shift+R</kbd> again to cancel.</div>${html}`;
+        } else {
+            html = `<div>Select a game to load it, or <kbd>Shift+R</kbd> again to cancel.</div>${html}`;
+        }
+        overlay.innerHTML = html;
+        for (var i = 0; i < len; i++) {
+            document.querySelector('#slot-'+CSS.escape(i)).onclick = function(e) { slotClick(e.target); }
+            document.querySelector('#slot-del-'+CSS.escape(i)).onclick = function(e) { slotDeleteClick(e.target); }
+        }
+    }
+
+    return {
+        get visible() { return visible; },
+        setVisibility(visibility) {
+            visible = visibility;
+            overlay.classList.toggle('visible', visibility);
+            if (visible) draw();
+        },
+        slotName(i) { return slots[i].name; },
+        redraw() { draw(); },
+    };
+}
+
+function showSlots() {
+    slotsOverlay.setVisibility(!slotsOverlay.visible);
+}
+
+function slotClick(button) {
+    var i = button.id.match(/(\d+)/)[0];
+    if (universe.load_from_slot(slotsOverlay.slotName(i))) {
+        slotsOverlay.setVisibility(false);
+        //might be another run altogether, so the map has to be fetched even on the same level
+        level = universe.level();
+        map = universe.get_tiles();
+    }
+    else {
+        console.log("Save could not be loaded, see the message log");
+    }
+    tick();
+}
+
+function slotDeleteClick(button) {
+    var i = button.id.match(/(\d+)/)[0];
+    var name = slotsOverlay.slotName(i);
+    if (window.confirm("Delete the saved game " + name + "?")) {
+        universe.delete_save_slot(name);
+        slotsOverlay.redraw();
+    }
+}
+
 function showViewList() {
 	//var set = inventoryOverlay.visible? false : true;
 	if (viewOverlay.visible) {
@@ -491,7 +568,7 @@ function onKeyDown(k) {
     else if (k == ut.KEY_R && !ut.isKeyPressed(ut.KEY_SHIFT)) cmd = rust.Command.Rest;
     else if (k == ut.KEY_PERIOD) //'r' is taken by 'rest' above 
     {
-        cmd = rust.Command.Wait; // dummy
+        //not Command.Wait, that passes a turn
         showRestView();
     }
     else if (k == ut.KEY_I) {
@@ -514,23 +591,14 @@ function onKeyDown(k) {
     }
     else if (k == ut.KEY_S) {
         cmd = rust.Command.SaveGame; //dummy
-        let save = universe.save_game();
-        // unfortunately we're using 0.0.6...
-        let storage = new Sifrr.Storage();
-        let data = {'save': save};
-        storage.set(data).then(() => {
+        if (universe.save_to_slot("quicksave")) {
             console.log("Saved game to browser...");
-        }) ;
+        }
     }
     else if (k == ut.KEY_R && ut.isKeyPressed(ut.KEY_SHIFT)) //'R'estore because L is taken by 'vikeys'
     {
         cmd = rust.Command.SaveGame; //dummy
-        let storage = new Sifrr.Storage(); //with the same (default) options, we access the same storage
-        storage.get('save').then(value => {
-            console.log("Loaded game: ", value)
-            //pass to Rust
-            universe.load_save(value.save);
-        });
+        showSlots();
     }
     else if (k == 27) // escape
     {
@@ -543,12 +611,16 @@ function onKeyDown(k) {
         if (document.getElementById("conversation").classList.contains('visible')) {
             document.getElementById("conversation").classList.toggle('visible', false); //close the conversation
         }
+        if (slotsOverlay.visible) {
+            slotsOverlay.setVisibility(false);
+        }
     }
 
     //if blocking screens are open, ignore keys
     if (document.getElementById("creation").classList.contains('visible') || 
     vendorOverlay.classList.contains('visible') || logOverlay.classList.contains('visible') || 
-    document.getElementById("conversation").classList.contains('visible') || document.getElementById("inventory").classList.contains('visible')) {
+    document.getElementById("conversation").classList.contains('visible') || document.getElementById("inventory").classList.contains('visible') ||
+    slotsOverlay.visible) {
         //console.log("Ignoring command because blocking screen is open...");
         cmd = -1;
     }
@@ -616,13 +688,51 @@ function onClickH(w_pos) {
 	tick();
 }
 
+//the game used to keep a single save in Sifrr.Storage, move it into a slot once
+async function importLegacySave() {
+    let storage = new Sifrr.Storage(); //with the same (default) options, we access the same storage
+    let value = await storage.get(['save', 'save_imported']);
+    if (value.save == undefined || value.save_imported) {
+        return;
+    }
+    //the old save stays where it was, just in case - the flag keeps a deleted slot from coming back
+    if (universe.import_save_slot("imported", value.save)) {
+        console.log("Imported the old save into the 'imported' slot");
+        await storage.set({'save_imported': true});
+    }
+}
+
 //logic shuffled to Rust (see load_datafiles())
 //needs to be async to be able to use await
 async function initGame(wasm) {
     universe = rust.Universe.new();
+    //reproducible games: index.html?seed=1234
+    var seed = new URLSearchParams(window.location.search).get("seed");
+    if (seed != null) {
+        universe.set_seed(BigInt(seed));
+    }
+    console.log("Seed: ", universe.seed());
+    //index.html?scenario=Night%20owl, the data file's first scenario otherwise
+    var scenario = new URLSearchParams(window.location.search).get("scenario");
+    if (scenario != null) {
+        universe.set_scenario(scenario);
+    }
     //async/await again to load text data
     //workaround
-    universe = await rust.load_datafile_ex(universe);
+    //mods go on top of the base game: index.html?mods=more_thugs.ron,neon.ron
+    var files = ["./data.ron", "./names.ron"];
+    var mods = new URLSearchParams(window.location.search).get("mods");
+    if (mods != null) {
+        files = files.concat(mods.split(",").map(m => "./mods/" + m));
+    }
+    universe = await rust.load_datafile_ex(universe, files);
+    //a broken data file means there is no game to render
+    var error = universe.data_error();
+    if (error != "") {
+        document.getElementById("game").textContent = "Could not load the game data: " + error;
+        return;
+    }
+    await importLegacySave();
 //     const res = await fetch("./npcs.ron");
 //     //console.log(res);
 //     const ron = await res.text();
@@ -638,8 +748,10 @@ function initRenderer(wasm) {
 
     //rust.load_datafile(universe); //we can't use things created from Rust this way as it'll cause null pointer error
 
-    // those are the map tiles, they don't change
+    // those are the map tiles, they only change with the level
     map = universe.get_tiles();
+    level = universe.level();
+    loadTilePalette();
     player = universe.player();
     g_wasm = wasm;
 
@@ -658,6 +770,7 @@ function initRenderer(wasm) {
     //init UI stuff
     inventoryOverlay = createInventoryOverlay();
     viewOverlay = createViewListOverlay();
+    slotsOverlay = createSlotsOverlay();
     vendorOverlay = document.getElementById("vendor");
     logOverlay = document.getElementById("log-history");
     //anonymous function
@@ -751,6 +864,7 @@ function initRenderer(wasm) {
     //universe.on_game_start();
     // character creation screen
     document.getElementById("creation").classList.toggle('visible', true);
+    fillScenarios();
     var lines = document.getElementById("creation").getElementsByTagName("li");
     for (var i = 0, len = lines.length; i < len; i++ ) {
         lines[i].children[0].onchange = function(e) { creationSelect(e.target); }
@@ -1,10 +1,10 @@
 use std::borrow::BorrowMut; //to be able to pass Path from system to function
 
 use super::log;
-use super::{game_message, path_to_player, path_to_target,
+use super::{path_to_player, path_to_target,
     Universe,
-    AI, Faction, FactionType, Vendor, Asleep, Player,
-    Point, Path, Map, RenderableGlyph};
+    AI, Faction, FactionType, Vendor, Asleep, Player, Speed, Level, on_level, COST_WALK, COST_ATTACK,
+    Point, Path, Map, GameRng};
 
 use hecs::Entity;
 
@@ -58,11 +58,49 @@ fn move_along_path(map: &mut Map, path: &mut Path, point: &mut Point) {
     }
 }
 
+//random movement
+fn wander(map: &mut Map, rng: &mut GameRng, point: &mut Point) {
+    let mut x = point.x;
+    let mut y = point.y;
+    let move_roll = rng.gen_range(1, 6);
+    match move_roll {
+        1 => x -= 1,
+        2 => x += 1,
+        3 => y -= 1,
+        4 => y += 1,
+        _ => {}
+    }
+
+    //move
+    let dest_idx = map.xy_idx(x, y);
+    if map.is_tile_walkable(x,y) && !map.is_tile_blocked(dest_idx) {
+        //check blockers
+        //let blocker = self.blocking_creatures_at(x as usize, y as usize);
+
+        //mark as blocked for pathfinding/AI
+        let old_idx = map.xy_idx(point.x, point.y);
+        map.clear_tile_blocked(old_idx);
+        map.set_tile_blocked(dest_idx);
+        //actually move
+        point.x = x;
+        point.y = y;
+    }
+}
+
 impl Universe {
-    //AI logic lives here!
+    //every level keeps going, not just the player's
     pub fn get_AI(&mut self) {
+        for level in 0..self.level_count() {
+            self.with_level(level, |u| u.level_AI(level));
+        }
+    }
+
+    //AI logic lives here!
+    //self.map is the given level, self.player_position and FOV are only valid if it's the player's level
+    fn level_AI(&mut self, level: usize) {
         let mut wants_path = Vec::new();
         let mut wants_sleep = Vec::new();
+        let mut wants_attack = Vec::new();
 
         // get the game time once
         //let time = self.get_time();
@@ -70,10 +108,18 @@ impl Universe {
         //log!("{}", &format!("Time: {}", time));
 
         // we need to borrow mutably (for the movement to happen), so we have to use a Point instead of two usizes (hecs limitation)
-        for (id, (ai, point)) in &mut self.ecs_world.query::<(&AI, &mut Point)>()
+        for (id, (ai, point, speed, lvl)) in &mut self.ecs_world.query::<(&AI, &mut Point, Option<&mut Speed>, Option<&Level>)>()
         .with::<String>()
         .iter()
          {
+            if !on_level(lvl, level) {
+                continue;
+            }
+            //not our turn yet
+            if speed.as_ref().map_or(false, |s| s.energy < 0) {
+                continue;
+            }
+            let attacks = wants_attack.len();
             //log!("{}", &format!("Got AI {} x {} y {}",  point.x, point.y, self.ecs_world.get::<String>(id).unwrap().to_string())); //just unwrapping isn't enough to format
             
             // exact movement depends on faction
@@ -85,40 +131,16 @@ impl Universe {
                     //28 800 is turns since midnight for 8:00h (game start)
                     if time < 39600+28800 {
                         if time > 25400 {
-                            //random movement
-                            let mut x = point.x;
-                            let mut y = point.y;
-                            //"A single instance is cached per thread and the returned ThreadRng is a reference to this instance" 
-                            let mut rng = rand::thread_rng();
-                            let move_roll = rng.gen_range(1, 6);
-                            match move_roll {
-                                1 => x -= 1,
-                                2 => x += 1,
-                                3 => y -= 1,
-                                4 => y += 1,
-                                _ => {}
-                            }
-
-                            //move
-                            let dest_idx = self.map.xy_idx(x, y);
-                            if self.map.is_tile_walkable(x,y) && !self.map.is_tile_blocked(dest_idx) {
-                                //check blockers
-                                //let blocker = self.blocking_creatures_at(x as usize, y as usize);
-
-                                //mark as blocked for pathfinding/AI
-                                let old_idx = self.map.xy_idx(point.x, point.y);
-                                self.map.clear_tile_blocked(old_idx);
-                                self.map.set_tile_blocked(dest_idx);
-                                //actually move
-                                point.x = x;
-                                point.y = y;
-                            }
+                            wander(&mut self.map, &mut self.rng, point);
                         } else if time > 21600 { // after 6:00h
                             //log!("Time to get up!");
                             // for some reason, some times are skipped, so we check
                             if self.ecs_world.get::<Asleep>(id).is_ok() {
                                 //hack solution: we know the one vendor is in pub
-                                for (ent_id, vendor) in self.ecs_world.query::<(&Vendor)>().iter() {
+                                for (ent_id, (vendor, vlvl)) in self.ecs_world.query::<(&Vendor, Option<&Level>)>().iter() {
+                                    if !on_level(vlvl, level) {
+                                        continue;
+                                    }
                                     let tg = self.ecs_world.get::<Point>(ent_id).unwrap();
                                     let path = path_to_target(&mut self.map, point.x as usize, point.y as usize, tg.x as usize, tg.y as usize);
 
@@ -147,9 +169,9 @@ impl Universe {
 
                         //if we don't have a bed yet...
                         if self.ecs_world.get::<Path>(id).is_err() {
-                            let beds = self.props_list_by_render(RenderableGlyph::Bed as u8);
+                            let beds = self.props_list_by_prefab("Bed");
                             let mut dists = Vec::new();
-                            for b in beds {
+                            for b in beds.into_iter().filter(|b| self.entity_level(*b) == level) {
                                 let pt = self.ecs_world.get::<Point>(b).unwrap();
                                 let dist = distance2d_chessboard(point.x, point.y, pt.x, pt.y);
                                 dists.push((b, dist));
@@ -157,8 +179,9 @@ impl Universe {
                             //sort by closest
                             dists.sort_by(|a,b| a.1.cmp(&b.1));
                             
-                            let pt = self.ecs_world.get::<Point>(dists[0].0).unwrap();
-                            if distance2d_chessboard(point.x, point.y, pt.x, pt.y) > 1 {
+                            //no beds on this map, just stay up
+                            let closest = dists.first().map(|d| *self.ecs_world.get::<Point>(d.0).unwrap());
+                            if let Some(pt) = closest.filter(|pt| distance2d_chessboard(point.x, point.y, pt.x, pt.y) > 1) {
                                 let path = path_to_target(&mut self.map, point.x as usize, point.y as usize, pt.x as usize, pt.y as usize);
                                 setup_path_and_step(&mut self.map, id, path, point, &mut wants_path);
 
@@ -179,6 +202,9 @@ impl Universe {
                         }
                    
 
+                } else if fact == FactionType::Enemy && level != self.level {
+                    //the player isn't around, just prowl
+                    wander(&mut self.map, &mut self.rng, point);
                 } else if fact == FactionType::Enemy {
                     //TODO: extract to a function: self.is_visible is the problem here... (map and player position can be passed quite easily)
 
@@ -187,17 +213,7 @@ impl Universe {
                     //log!("{}", &format!("Player pos x {} y {}", player_pos.0, player_pos.1));
                     if distance2d_chessboard(point.x, point.y, player_pos.0, player_pos.1) < 2 {
                         //log!("{}", &format!("AI next to player, attack!"));
-                        game_message(&format!("{{rAI {} kicked at the player", self.ecs_world.get::<String>(id).unwrap().to_string()));
-                        //get player entity
-                        let mut play: Option<Entity> = None;
-                        for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
-                            play = Some(id);
-                        }
-                        match play {
-                            Some(entity) => self.attack(&entity),
-                            None => {},
-                        }
-                        
+                        wants_attack.push(id);
                     } else {
                         //can we see the player? (assumes symmetric FOV)
                         if self.is_visible(point.x as usize, point.y as usize) {
@@ -205,17 +221,7 @@ impl Universe {
                             // move or attack            
                             if new_pos.0 == player_pos.0 as usize && new_pos.1 == player_pos.1 as usize {
                                 //log!("{}", &format!("new: {} {} player: {} {}", new_pos.0, new_pos.1, player_pos.0, player_pos.1));
-                                game_message(&format!("{{rAI {} kicked at the player", self.ecs_world.get::<String>(id).unwrap().to_string()));
-                                //get player entity
-                                let mut play: Option<Entity> = None;
-                                for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
-                                    play = Some(id);
-                                }
-                                match play {
-                                    Some(entity) => self.attack(&entity),
-                                    None => {},
-                                }
-
+                                wants_attack.push(id);
                             } else {
                                 //actually move
                                 point.x = new_pos.0 as i32;
@@ -229,6 +235,11 @@ impl Universe {
                 }
             }
 
+            //pay for whatever we did (idling costs the same as walking)
+            if let Some(speed) = speed {
+                let walk = (COST_WALK as f32 * self.map.move_cost(self.map.xy_idx(point.x, point.y))) as i32;
+                speed.energy -= if wants_attack.len() > attacks { COST_ATTACK } else { walk };
+            }
         }
         
         //postponed stuff to here since we can't add components while iterating
@@ -248,5 +259,17 @@ impl Universe {
         for id in wants_sleep {
             self.ecs_world.insert_one(id, Asleep{});
         }
+        // attacking rolls dice, and the RNG can't be borrowed while iterating
+        for id in wants_attack {
+            //get player entity
+            let mut play: Option<Entity> = None;
+            for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
+                play = Some(id);
+            }
+            match play {
+                Some(entity) => self.attack(&id, &entity),
+                None => {},
+            }
+        }
     }
 }
\ No newline at end of file
@@ -22,37 +22,37 @@ fn get_available_neighbors(map: &Map, idx:usize) -> Vec<(usize, f32)> {
     // Cardinal directions
     if map.is_in_bounds(x-1, y) && map.is_tile_walkable(x-1, y) { 
         let idx = neighbor_idx(map, x,y, -1,0);
-        neighbors.push((idx, 1.0)) 
+        neighbors.push((idx, 1.0 * map.move_cost(idx))) 
     };
     if map.is_in_bounds(x+1, y) && map.is_tile_walkable(x+1, y) { 
         let idx = neighbor_idx(map, x,y, 1, 0);
-        neighbors.push((idx, 1.0)) 
+        neighbors.push((idx, 1.0 * map.move_cost(idx))) 
     };
     if map.is_in_bounds(x, y-1) && map.is_tile_walkable(x, y-1) { 
         let idx = neighbor_idx(map, x,y, 0, -1);
-        neighbors.push((idx, 1.0)) 
+        neighbors.push((idx, 1.0 * map.move_cost(idx))) 
     };
     if map.is_in_bounds(x, y+1) && map.is_tile_walkable(x, y+1) { 
         let idx = neighbor_idx(map, x,y, 0, 1);
-        neighbors.push((idx, 1.0)) 
+        neighbors.push((idx, 1.0 * map.move_cost(idx))) 
     };
 
     // Diagonals
     if map.is_in_bounds(x-1, y-1) && map.is_tile_walkable(x-1, y-1) { 
         let idx = neighbor_idx(map, x,y, -1, -1);
-        neighbors.push((idx, 1.4)); 
+        neighbors.push((idx, 1.4 * map.move_cost(idx))); 
     }
     if map.is_in_bounds(x+1, y-1) && map.is_tile_walkable(x+1, y-1) { 
         let idx = neighbor_idx(map, x,y, 1, -1);
-        neighbors.push((idx, 1.4)); 
+        neighbors.push((idx, 1.4 * map.move_cost(idx))); 
     }
     if map.is_in_bounds(x-1, y+1) && map.is_tile_walkable(x-1, y+1) { 
         let idx = neighbor_idx(map, x,y, -1, 1);
-        neighbors.push((idx, 1.4)); 
+        neighbors.push((idx, 1.4 * map.move_cost(idx))); 
     }
     if map.is_in_bounds(x+1, y+1) && map.is_tile_walkable(x+1, y+1) { 
         let idx = neighbor_idx(map, x, y, 1, 1);
-        neighbors.push((idx, 1.4)); 
+        neighbors.push((idx, 1.4 * map.move_cost(idx))); 
     }
 
     return neighbors;
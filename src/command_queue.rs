@@ -0,0 +1,60 @@
+//commands waiting to be processed, from JS, UI callbacks (e.g. conversations) and the debug console
+//replaces the old GLOBAL_INPUT static mut, which could only hold one command
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use serde::{Serialize, Deserialize};
+
+use super::Command;
+
+// clones share the same queue, so UI closures can hold one and push into it later
+#[derive(Clone, Default)]
+pub struct CommandQueue {
+    queue: Rc<RefCell<VecDeque<Command>>>,
+}
+
+impl CommandQueue {
+    pub fn new() -> CommandQueue {
+        Default::default()
+    }
+
+    pub fn push(&self, cmd: Command) {
+        //dummy, JS sends those just to keep us going
+        if cmd == Command::None {
+            return;
+        }
+        self.queue.borrow_mut().push_back(cmd);
+    }
+
+    pub fn pop(&self) -> Option<Command> {
+        self.queue.borrow_mut().pop_front()
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.queue.borrow().len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.queue.borrow().is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CommandResult {
+    Consumed,
+    Rejected(String), //reason
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandReport {
+    pub command: Command,
+    pub result: CommandResult,
+}
+
+// the names the enum uses, e.g. "MoveLeft" - for scripts and the debug console
+pub fn command_from_name(name: &str) -> Option<Command> {
+    serde_json::from_str(&format!("\"{}\"", name)).ok()
+}
@@ -3,13 +3,20 @@ use wasm_bindgen_futures::JsFuture;
 use web_sys::{Request, RequestInit, RequestMode, Response};
 use wasm_bindgen::JsCast; // for dyn_into
 
+use hecs::{World, Entity};
+
 use serde::{Serialize, Deserialize};
 
 use super::log;
-use super::{Universe, Cell, RenderableGlyph, AI, Faction, CombatStats,
-Item, Equippable, DefenseBonus};
+use super::map_builders::BuildingTag;
+use super::map::{Map, TileDef, BUILDER_TILES};
+use super::npc_name::NameCulture;
+use super::{Universe, Renderable, Needs, SpawnedFrom, AI, Faction, CombatStats, Speed, Vendor, Conversation, Attributes, Money,
+Item, Consumable, ProvidesHealing, ProvidesFood, ProvidesQuench, Equippable, MeleeBonus, DefenseBonus, ToRemove};
 
 use std::sync::Mutex;
+use std::fmt;
+use std::collections::HashMap;
 
 
 //what it says
@@ -17,26 +24,521 @@ use std::sync::Mutex;
 pub struct DataMaster {
     pub npcs : Vec<NPCPrefab>,
     pub items: Vec<ItemPrefab>,
+    #[serde(default)]
+    pub props: Vec<PropPrefab>,
     pub map : MapConfig,
+    #[serde(default)]
+    pub start_spawns: Vec<SpawnPoint>, //placed by hand, on top of what the map builder spawns
+    #[serde(default)]
+    pub spawn_tables: Vec<SpawnTable>,
+    #[serde(default)]
+    pub tiles: Vec<TileDef>,
+    #[serde(default)]
+    pub glyphs: Vec<GlyphDef>,
+    #[serde(default)]
+    pub name_cultures: Vec<NameCulture>,
+    #[serde(default)]
+    pub scenarios: Vec<Scenario>,
+    #[serde(skip)]
+    pub sources: HashMap<String, String>, //prefab name -> the file its definition came from
+    #[serde(skip)]
+    pub files: Vec<String>, //in the order they were merged, for reloading
+}
+
+//one data file: the base game, or a mod that only lists what it adds or changes
+// prefabs with a name that's already there replace it, new ones get appended
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DataLayer {
+    pub npcs: Vec<NPCPrefab>,
+    pub items: Vec<ItemPrefab>,
+    pub props: Vec<PropPrefab>,
+    pub map: Option<MapConfig>, //the last file that has one wins
+    pub start_spawns: Vec<SpawnPoint>, //appended
+    pub spawn_tables: Vec<SpawnTable>, //appended
+    pub tiles: Vec<TileDef>, //replaced by name
+    pub glyphs: Vec<GlyphDef>, //replaced by name
+    pub name_cultures: Vec<NameCulture>, //replaced by name
+    pub scenarios: Vec<Scenario>, //replaced by name
+}
+
+//how a new game starts; picked before the data loads (see Universe::set_scenario), the first one otherwise
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub combat: CombatStats,
+    pub attributes: Attributes,
+    pub needs: Needs,
+    pub money: f32,
+    #[serde(default)]
+    pub inventory: Vec<String>, //item names, in the backpack
+    pub start_time: (u32, u32), //hour and minute
+    #[serde(default = "default_start_building")]
+    pub start_building: BuildingTag,
+    #[serde(default)]
+    pub intro: String, //game message, can have markup
+}
+
+fn default_start_building() -> BuildingTag { BuildingTag::Pub }
+
+impl Scenario {
+    //seconds after midnight
+    pub fn start_seconds(&self) -> i64 {
+        (self.start_time.0 * 3600 + self.start_time.1 * 60) as i64
+    }
+}
+
+//what JS draws for an entity; prefabs refer to these by name, Renderable and saves store the id
+// ids have to stay the same once they're out there, or old saves get the wrong glyphs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GlyphDef {
+    pub id: u8,
+    pub name: String,
+    pub glyph: String,
+    pub fg: (u8, u8, u8),
+    #[serde(default)]
+    pub bg: Option<(u8, u8, u8)>,
+    #[serde(default)]
+    pub tile: Option<(u32, u32)>, //column and row in a tileset, for renderers that have one
 }
 
 
-#[derive(Serialize, Deserialize)]
+// prefabs can name a parent of the same kind (NPC, item, prop) and only list what's different
+// templates are only there to be inherited from, they can't be spawned
+#[derive(Serialize, Deserialize, Clone)]
 pub struct NPCPrefab {
     pub name: String,
-    pub renderable: RenderableGlyph,
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub template: bool,
+    pub renderable: Option<String>, //required, but can come from the parent
     pub ai: Option<AI>,
     pub faction: Option<Faction>, 
     pub combat: Option<CombatStats>,
+    pub speed: Option<i32>, //100 is normal
+    pub vendor: Option<Vendor>,
+    pub conversation: Option<Conversation>,
+    pub attributes: Option<Attributes>,
+    pub money: Option<Money>,
+    pub names: Option<NameStyle>, //gets a NPCName, rolled on spawn
+    pub equipment: Option<Vec<String>>, //item names, spawned equipped
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ItemPrefab {
     pub name: String,
-    pub renderable: RenderableGlyph,
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub template: bool,
+    pub renderable: Option<String>,
     pub item: Option<Item>,
     pub equippable: Option<Equippable>,
     pub defense: Option<DefenseBonus>,
+    pub melee: Option<MeleeBonus>,
+    pub consumable: Option<Consumable>,
+    pub heals: Option<ProvidesHealing>,
+    pub food: Option<ProvidesFood>,
+    pub quench: Option<ProvidesQuench>,
+}
+
+//which name list a NPC's name comes from, and how likely it is to be a male name
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NameStyle {
+    pub culture: String,
+    #[serde(default = "default_male")]
+    pub male: f64, //0 to 1
+}
+
+fn default_male() -> f64 { 0.5 }
+
+//anything the entity builder can put together (see Universe::build_entity)
+pub trait Prefab {
+    fn name(&self) -> &str;
+    fn renderable(&self) -> &str; //a name from the glyph registry
+    fn add_components(&self, builder: &mut hecs::EntityBuilder);
+}
+
+impl Prefab for NPCPrefab {
+    fn name(&self) -> &str { &self.name }
+    fn renderable(&self) -> &str { self.renderable.as_deref().unwrap() } //checked by resolve_prefabs
+
+    fn add_components(&self, builder: &mut hecs::EntityBuilder) {
+        builder.add(self.name.to_string());
+        if let Some(ai) = self.ai { builder.add(ai); }
+        if let Some(faction) = self.faction { builder.add(faction); }
+        if let Some(combat) = self.combat { builder.add(combat); }
+        if let Some(speed) = self.speed { builder.add(Speed{speed: speed, energy: 0}); }
+        if let Some(vendor) = self.vendor { builder.add(vendor); }
+        if let Some(conversation) = &self.conversation { builder.add(conversation.clone()); }
+        if let Some(attributes) = self.attributes { builder.add(attributes); }
+        if let Some(money) = self.money { builder.add(money); }
+    }
+}
+
+impl Prefab for ItemPrefab {
+    fn name(&self) -> &str { &self.name }
+    fn renderable(&self) -> &str { self.renderable.as_deref().unwrap() } //checked by resolve_prefabs
+
+    fn add_components(&self, builder: &mut hecs::EntityBuilder) {
+        builder.add(self.name.to_string());
+        builder.add(ToRemove{yes:false});
+        if let Some(item) = self.item { builder.add(item); }
+        if let Some(equippable) = self.equippable { builder.add(equippable); }
+        if let Some(defense) = self.defense { builder.add(defense); }
+        if let Some(melee) = self.melee { builder.add(melee); }
+        if let Some(consumable) = self.consumable { builder.add(consumable); }
+        if let Some(heals) = self.heals { builder.add(heals); }
+        if let Some(food) = self.food { builder.add(food); }
+        if let Some(quench) = self.quench { builder.add(quench); }
+    }
+}
+
+impl Prefab for PropPrefab {
+    fn name(&self) -> &str { &self.name }
+    fn renderable(&self) -> &str { self.renderable.as_deref().unwrap() } //checked by resolve_prefabs
+
+    //props don't have names
+    fn add_components(&self, _builder: &mut hecs::EntityBuilder) {}
+}
+
+//-----------------------------------------------------------------------------
+//inheritance
+pub trait Inherit {
+    fn name(&self) -> &str;
+    fn parent(&self) -> Option<&str>;
+    fn is_template(&self) -> bool;
+    fn has_renderable(&self) -> bool;
+    //fills in whatever this one doesn't set itself
+    fn inherit_from(&mut self, parent: &Self);
+}
+
+//Option::or, for struct fields
+macro_rules! inherit {
+    ($child:ident, $parent:ident, $($field:ident),*) => {
+        $( if $child.$field.is_none() { $child.$field = $parent.$field.clone(); } )*
+    };
+}
+
+impl Inherit for NPCPrefab {
+    fn name(&self) -> &str { &self.name }
+    fn parent(&self) -> Option<&str> { self.parent.as_deref() }
+    fn is_template(&self) -> bool { self.template }
+    fn has_renderable(&self) -> bool { self.renderable.is_some() }
+    fn inherit_from(&mut self, parent: &NPCPrefab) {
+        inherit!(self, parent, renderable, ai, faction, combat, speed, vendor, conversation, attributes, money, names, equipment);
+    }
+}
+
+impl Inherit for ItemPrefab {
+    fn name(&self) -> &str { &self.name }
+    fn parent(&self) -> Option<&str> { self.parent.as_deref() }
+    fn is_template(&self) -> bool { self.template }
+    fn has_renderable(&self) -> bool { self.renderable.is_some() }
+    fn inherit_from(&mut self, parent: &ItemPrefab) {
+        inherit!(self, parent, renderable, item, equippable, defense, melee, consumable, heals, food, quench);
+    }
+}
+
+impl Inherit for PropPrefab {
+    fn name(&self) -> &str { &self.name }
+    fn parent(&self) -> Option<&str> { self.parent.as_deref() }
+    fn is_template(&self) -> bool { self.template }
+    fn has_renderable(&self) -> bool { self.renderable.is_some() }
+    fn inherit_from(&mut self, parent: &PropPrefab) {
+        inherit!(self, parent, renderable);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DataError {
+    Fetch(String), //the file couldn't be downloaded
+    Parse(String), //not valid RON, or doesn't match DataMaster
+    MissingParent { prefab: String, parent: String },
+    ParentCycle(Vec<String>), //the chain of names, ending where it started
+    MissingField { prefab: String, field: String },
+    DuplicateName(String),
+    MissingMap, //none of the files has a map section
+    BadSpawnTable { key: String, problem: String },
+    MissingTile(String),
+    BadTile { tile: String, problem: String },
+    UnknownGlyph { prefab: String, glyph: String },
+    DuplicateGlyph(u8),
+    BadNames { prefab: String, problem: String },
+    MissingScenario,
+    BadScenario { scenario: String, problem: String },
+    UnknownPrefab { referenced_by: String, name: String }, //e.g. equipment that isn't in items
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataError::Fetch(e) => write!(f, "could not fetch the data file: {}", e),
+            DataError::Parse(e) => write!(f, "data file is malformed: {}", e),
+            DataError::MissingParent { prefab, parent } => write!(f, "prefab {} has parent {}, which doesn't exist", prefab, parent),
+            DataError::ParentCycle(chain) => write!(f, "prefabs inherit from each other in a loop: {}", chain.join(" -> ")),
+            DataError::MissingField { prefab, field } => write!(f, "prefab {} has no {} (and neither do its parents)", prefab, field),
+            DataError::DuplicateName(name) => write!(f, "there is more than one prefab called {}", name),
+            DataError::MissingMap => write!(f, "none of the data files has a map section"),
+            DataError::BadSpawnTable { key, problem } => write!(f, "spawn table for {}: {}", key, problem),
+            DataError::MissingTile(name) => write!(f, "no tile called {}", name),
+            DataError::BadTile { tile, problem } => write!(f, "tile {}: {}", tile, problem),
+            DataError::UnknownGlyph { prefab, glyph } => write!(f, "{} uses glyph {}, which isn't in the glyph list", prefab, glyph),
+            DataError::DuplicateGlyph(id) => write!(f, "more than one glyph has id {}", id),
+            DataError::BadNames { prefab, problem } => write!(f, "names for {}: {}", prefab, problem),
+            DataError::MissingScenario => write!(f, "none of the data files has a scenario"),
+            DataError::BadScenario { scenario, problem } => write!(f, "scenario {}: {}", scenario, problem),
+            DataError::UnknownPrefab { referenced_by, name } => write!(f, "{} refers to {}, which isn't defined", referenced_by, name),
+        }
+    }
+}
+
+// where in which file it went wrong
+#[derive(Debug, PartialEq)]
+pub struct DataLoadError {
+    pub file: String,
+    pub line: usize, //1-based, 0 if we don't know
+    pub column: usize,
+    pub error: DataError,
+}
+
+impl fmt::Display for DataLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.error)
+        } else {
+            write!(f, "{}: {}", self.file, self.error)
+        }
+    }
+}
+
+impl DataError {
+    //the prefab the error is about, and the sections it is defined in, to find it in the file
+    // no sections means it isn't defined anywhere, so it's found where it's used
+    fn subject(&self) -> Option<(&str, &'static [&'static str])> {
+        const PREFABS : &[&str] = &["npcs", "items", "props"];
+        match self {
+            DataError::MissingParent { prefab, .. } => Some((prefab, PREFABS)),
+            DataError::ParentCycle(chain) => chain.first().map(|s| (s.as_str(), PREFABS)),
+            DataError::MissingField { prefab, .. } => Some((prefab, PREFABS)),
+            DataError::DuplicateName(name) => Some((name, PREFABS)),
+            DataError::UnknownPrefab { name, .. } => Some((name, &[])),
+            DataError::UnknownGlyph { prefab, .. } => Some((prefab, PREFABS)),
+            DataError::BadNames { prefab, .. } => Some((prefab, PREFABS)),
+            DataError::BadScenario { scenario, .. } => Some((scenario, &["scenarios"])),
+            DataError::BadTile { tile, .. } => Some((tile, &["tiles"])),
+            _ => None,
+        }
+    }
+}
+
+fn line_column(source: &str, idx: usize) -> (usize, usize) {
+    let before = &source[..idx];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
+}
+
+//where `name: "X"` is, in the given top level sections (npcs, items...)
+// glyphs, spawn tables and equipment lists use the same names, so just looking for "X" finds the wrong line
+fn definitions(source: &str, sections: &[&str], name: &str) -> Vec<usize> {
+    let quoted = format!("\"{}\"", name);
+    let mut found = Vec::new();
+    let mut depth = 0;
+    let mut section = "";
+    let mut last_ident = ""; //the last one, until something else than whitespace comes along
+    let mut chars = source.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                //the string, backslashes escape
+                let mut escaped = false;
+                while let Some((_, c)) = chars.next() {
+                    if c == '"' && !escaped { break; }
+                    escaped = c == '\\' && !escaped;
+                }
+                last_ident = "";
+            },
+            '/' if source[i..].starts_with("//") => {
+                while let Some(&(_, c)) = chars.peek() {
+                    if c == '\n' { break; }
+                    chars.next();
+                }
+            },
+            '(' | '[' | '{' => { depth += 1; last_ident = ""; },
+            ')' | ']' | '}' => { depth -= 1; last_ident = ""; },
+            ':' => {
+                if depth == 1 && !last_ident.is_empty() {
+                    section = last_ident;
+                }
+                if last_ident == "name" && sections.contains(&section) && source[i+1..].trim_start().starts_with(&quoted) {
+                    found.push(i + 1 + (source[i+1..].len() - source[i+1..].trim_start().len()));
+                }
+                last_ident = "";
+            },
+            c if c.is_alphanumeric() || c == '_' => {
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') { break; }
+                    chars.next();
+                }
+                let end = chars.peek().map_or(source.len(), |&(j, _)| j);
+                last_ident = &source[i..end];
+            },
+            c if c.is_whitespace() => {},
+            _ => { last_ident = ""; },
+        }
+    }
+    return found;
+}
+
+//line and column of the nth (0-based) definition of "name", or where it's first used if it isn't defined anywhere; (0, 0) if it isn't there at all
+fn locate(source: &str, sections: &[&str], name: &str, nth: usize) -> (usize, usize) {
+    let found = if sections.is_empty() {
+        source.match_indices(&format!("\"{}\"", name)).map(|(i, _)| i).collect()
+    } else {
+        definitions(source, sections, name)
+    };
+    match found.get(nth) {
+        Some(&idx) => line_column(source, idx),
+        None => (0, 0),
+    }
+}
+
+fn merge_list<T: Inherit>(list: &mut Vec<T>, new: Vec<T>, file: &str, sources: &mut HashMap<String, String>) -> Result<(), DataError> {
+    //twice in the same file is still a mistake
+    let mut seen : Vec<String> = Vec::new();
+    for p in new {
+        let name = p.name().to_string();
+        if seen.contains(&name) {
+            return Err(DataError::DuplicateName(name));
+        }
+        seen.push(name.clone());
+        match list.iter().position(|e| e.name() == name) {
+            Some(i) => {
+                log!("{}", &format!("{} overrides {} from {}", file, name, sources.get(&name).map_or("?", |s| s.as_str())));
+                list[i] = p;
+            },
+            None => list.push(p),
+        }
+        sources.insert(name, file.to_string());
+    }
+    Ok(())
+}
+
+// parents get resolved first, so that grandparents' fields come through, too
+fn resolve_list<T: Inherit + Clone>(list: &mut Vec<T>) -> Result<(), DataError> {
+    let mut done = vec![false; list.len()];
+    for i in 0..list.len() {
+        resolve_one(list, i, &mut done, &mut Vec::new())?;
+    }
+    //templates are allowed to be incomplete
+    for p in list.iter().filter(|p| !p.is_template()) {
+        if !p.has_renderable() {
+            return Err(DataError::MissingField{ prefab: p.name().to_string(), field: "renderable".to_string() });
+        }
+    }
+    Ok(())
+}
+
+fn resolve_one<T: Inherit + Clone>(list: &mut Vec<T>, i: usize, done: &mut Vec<bool>, chain: &mut Vec<String>) -> Result<(), DataError> {
+    if done[i] {
+        return Ok(());
+    }
+    let name = list[i].name().to_string();
+    if chain.contains(&name) {
+        chain.push(name);
+        return Err(DataError::ParentCycle(chain.clone()));
+    }
+    if let Some(parent) = list[i].parent() {
+        let p = match list.iter().position(|e| e.name() == parent) {
+            Some(p) => p,
+            None => return Err(DataError::MissingParent{ prefab: name, parent: parent.to_string() }),
+        };
+        chain.push(name);
+        resolve_one(list, p, done, chain)?;
+        chain.pop();
+        let parent = list[p].clone();
+        list[i].inherit_from(&parent);
+    }
+    done[i] = true;
+    Ok(())
+}
+
+//furniture and the like - no name, can't be picked up
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PropPrefab {
+    pub name: String,
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub template: bool,
+    pub renderable: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpawnPoint {
+    pub x: i32,
+    pub y: i32,
+    pub name: String,
+}
+
+//where on the map a spawn table applies
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum SpawnKey {
+    Building(BuildingTag),
+    Zone(ZoneType), //outside of the tagged buildings
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ZoneType {
+    Indoors,
+    Streets,
+    Outskirts,
+}
+
+impl ZoneType {
+    pub fn of(map: &Map, idx: usize) -> ZoneType {
+        if map.is_tile(idx, "floor") {
+            ZoneType::Indoors
+        } else if map.is_tile(idx, "pavement") || map.is_tile(idx, "door") {
+            ZoneType::Streets
+        } else {
+            ZoneType::Outskirts
+        }
+    }
+}
+
+// rolled when the map is built: between min and max times (or until the area is full, with fill), picking an entry by weight each time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpawnTable {
+    pub key: SpawnKey,
+    #[serde(default)]
+    pub min: u32,
+    #[serde(default)]
+    pub max: u32,
+    #[serde(default)]
+    pub fill: bool, //one on every free tile instead, min and max don't matter
+    pub entries: Vec<SpawnEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpawnEntry {
+    pub name: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    pub hours: Option<(u32, u32)>, //only spawns from the first hour until the second, can wrap past midnight
+}
+
+fn default_weight() -> u32 { 1 }
+
+impl SpawnEntry {
+    pub fn active_at(&self, hour: u32) -> bool {
+        match self.hours {
+            None => true,
+            Some((from, to)) if from <= to => hour >= from && hour < to,
+            Some((from, to)) => hour >= from || hour < to,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -47,13 +549,15 @@ pub struct MapConfig {
     pub gain: f32,
     pub lacuna: f32,
     pub frequency: f32,
-    pub ter_wall: Cell,
-    pub ter_ground: Cell,
-    pub ter_bound: Cell,
+    pub ter_wall: String, //tile names
+    pub ter_ground: String,
+    pub ter_bound: String,
 }
 
 lazy_static! {
     pub static ref DATA: Mutex<DataMaster> = Mutex::new(DataMaster::empty());
+    //filled in by the async reload, picked up by Universe::apply_pending_reload
+    pub static ref PENDING_RELOAD: Mutex<Option<Result<DataMaster, DataLoadError>>> = Mutex::new(None);
 }
 
 
@@ -62,66 +566,461 @@ impl DataMaster {
         DataMaster {
             npcs: Vec::new(),
             items: Vec::new(),
+            props: Vec::new(),
             map: MapConfig{width:2, height:2, octaves:1, gain:0.5,lacuna:0.5, frequency:0.5, 
-                ter_wall: Cell::Tree, ter_ground: Cell::Grass, ter_bound: Cell::Mountain}, //dummy
+                ter_wall: "tree".to_string(), ter_ground: "grass".to_string(), ter_bound: "mountain".to_string()}, //dummy
+            start_spawns: Vec::new(),
+            spawn_tables: Vec::new(),
+            tiles: Vec::new(),
+            glyphs: Vec::new(),
+            name_cultures: Vec::new(),
+            scenarios: Vec::new(),
+            sources: HashMap::new(),
+            files: Vec::new(),
+        }
+    }
+
+    pub fn glyph_id(&self, name: &str) -> Option<u8> {
+        self.glyphs.iter().find(|g| g.name == name).map(|g| g.id)
+    }
+
+    pub fn find_culture(&self, name: &str) -> Option<&NameCulture> {
+        self.name_cultures.iter().find(|c| c.name == name)
+    }
+
+    //None, or a name that isn't there, gets the first one
+    pub fn find_scenario(&self, name: Option<&str>) -> Option<&Scenario> {
+        name.and_then(|n| self.scenarios.iter().find(|s| s.name == n)).or(self.scenarios.first())
+    }
+
+    pub fn can_spawn(&self, name: &str) -> bool {
+        self.find_npc(name).is_some() || self.find_item(name).is_some() || self.find_prop(name).is_some()
+    }
+
+    pub fn source_of(&self, name: &str) -> Option<&str> {
+        self.sources.get(name).map(|s| s.as_str())
+    }
+
+    // same names replace what's there (keeping its place in the list), new ones get appended
+    fn merge_layer(&mut self, layer: DataLayer, file: &str) -> Result<(), DataError> {
+        merge_list(&mut self.npcs, layer.npcs, file, &mut self.sources)?;
+        merge_list(&mut self.items, layer.items, file, &mut self.sources)?;
+        merge_list(&mut self.props, layer.props, file, &mut self.sources)?;
+        if let Some(map) = layer.map {
+            self.map = map;
+        }
+        self.start_spawns.extend(layer.start_spawns);
+        self.spawn_tables.extend(layer.spawn_tables);
+        for tile in layer.tiles {
+            match self.tiles.iter().position(|t| t.name == tile.name) {
+                Some(i) => self.tiles[i] = tile,
+                None => self.tiles.push(tile),
+            }
+        }
+        for glyph in layer.glyphs {
+            match self.glyphs.iter().position(|g| g.name == glyph.name) {
+                Some(i) => self.glyphs[i] = glyph,
+                None => self.glyphs.push(glyph),
+            }
+        }
+        for culture in layer.name_cultures {
+            match self.name_cultures.iter().position(|c| c.name == culture.name) {
+                Some(i) => self.name_cultures[i] = culture,
+                None => self.name_cultures.push(culture),
+            }
+        }
+        for scenario in layer.scenarios {
+            match self.scenarios.iter().position(|s| s.name == scenario.name) {
+                Some(i) => self.scenarios[i] = scenario,
+                None => self.scenarios.push(scenario),
+            }
+        }
+        Ok(())
+    }
+
+    //templates don't count
+    pub fn find_npc(&self, name: &str) -> Option<&NPCPrefab> {
+        self.npcs.iter().find(|p| p.name == name && !p.template)
+    }
+
+    pub fn find_item(&self, name: &str) -> Option<&ItemPrefab> {
+        self.items.iter().find(|p| p.name == name && !p.template)
+    }
+
+    pub fn find_prop(&self, name: &str) -> Option<&PropPrefab> {
+        self.props.iter().find(|p| p.name == name && !p.template)
+    }
+
+    //everything that can be spawned, for the debug console
+    pub fn prefab_names(&self) -> Vec<String> {
+        self.npcs.iter().filter(|p| !p.template).map(|p| p.name.clone())
+        .chain(self.items.iter().filter(|p| !p.template).map(|p| p.name.clone()))
+        .chain(self.props.iter().filter(|p| !p.template).map(|p| p.name.clone()))
+        .collect()
+    }
+
+    // things that parse fine, but would break the game later on
+    pub fn validate(&self) -> Result<(), DataError> {
+        //spawn() looks names up in all three lists, so they share one namespace
+        let names : Vec<&str> = self.npcs.iter().map(|p| p.name.as_str())
+            .chain(self.items.iter().map(|p| p.name.as_str()))
+            .chain(self.props.iter().map(|p| p.name.as_str())).collect();
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return Err(DataError::DuplicateName(name.to_string()));
+            }
+        }
+
+        for npc in self.npcs.iter() {
+            for item in npc.equipment.iter().flatten() {
+                if self.find_item(item).is_none() {
+                    return Err(DataError::UnknownPrefab{ referenced_by: npc.name.clone(), name: item.clone() });
+                }
+            }
+        }
+
+        for npc in self.npcs.iter().filter(|p| !p.template) {
+            if let Some(style) = &npc.names {
+                let bad = |problem: String| Err(DataError::BadNames{ prefab: npc.name.clone(), problem: problem });
+                let culture = match self.find_culture(&style.culture) {
+                    Some(c) => c,
+                    None => return bad(format!("there is no {} name list", style.culture)),
+                };
+                if !(0.0..=1.0).contains(&style.male) || !(0.0..=1.0).contains(&culture.nickname_chance) {
+                    return bad("chances go from 0 to 1".to_string());
+                }
+                if culture.surnames.is_empty() || (style.male > 0.0 && culture.male.is_empty()) || (style.male < 1.0 && culture.female.is_empty()) {
+                    return bad(format!("the {} name list is missing names", culture.name));
+                }
+            }
+        }
+
+        if self.scenarios.is_empty() {
+            return Err(DataError::MissingScenario);
+        }
+        for scenario in self.scenarios.iter() {
+            for item in scenario.inventory.iter() {
+                if self.find_item(item).is_none() {
+                    return Err(DataError::UnknownPrefab{ referenced_by: format!("scenario {}", scenario.name), name: item.clone() });
+                }
+            }
+            if scenario.start_time.0 > 23 || scenario.start_time.1 > 59 {
+                return Err(DataError::BadScenario{ scenario: scenario.name.clone(), problem: format!("no such time as {:02}:{:02}", scenario.start_time.0, scenario.start_time.1) });
+            }
+        }
+
+        for sp in self.start_spawns.iter() {
+            if !self.can_spawn(&sp.name) {
+                return Err(DataError::UnknownPrefab{ referenced_by: "start_spawns".to_string(), name: sp.name.clone() });
+            }
+        }
+
+        for (i, glyph) in self.glyphs.iter().enumerate() {
+            if self.glyphs[..i].iter().any(|g| g.id == glyph.id) {
+                return Err(DataError::DuplicateGlyph(glyph.id));
+            }
+        }
+        let renderables = self.npcs.iter().map(|p| (&p.name, &p.renderable))
+            .chain(self.items.iter().map(|p| (&p.name, &p.renderable)))
+            .chain(self.props.iter().map(|p| (&p.name, &p.renderable)));
+        for (name, renderable) in renderables {
+            if let Some(glyph) = renderable {
+                if self.glyph_id(glyph).is_none() {
+                    return Err(DataError::UnknownGlyph{ prefab: name.clone(), glyph: glyph.clone() });
+                }
+            }
+        }
+
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if let Some(other) = self.tiles[..i].iter().find(|t| t.id == tile.id) {
+                return Err(DataError::BadTile{ tile: tile.name.clone(), problem: format!("id {} is already {}", tile.id, other.name) });
+            }
+            if tile.cost <= 0.0 {
+                return Err(DataError::BadTile{ tile: tile.name.clone(), problem: format!("cost {} has to be above 0", tile.cost) });
+            }
+        }
+        let terrain = [&self.map.ter_wall, &self.map.ter_ground, &self.map.ter_bound];
+        for name in BUILDER_TILES.iter().cloned().chain(terrain.iter().map(|t| t.as_str())) {
+            if !self.tiles.iter().any(|t| t.name == name) {
+                return Err(DataError::MissingTile(name.to_string()));
+            }
+        }
+
+        for table in self.spawn_tables.iter() {
+            let key = format!("{:?}", table.key);
+            if table.min > table.max {
+                return Err(DataError::BadSpawnTable{ key: key, problem: format!("min {} is more than max {}", table.min, table.max) });
+            }
+            for e in table.entries.iter() {
+                if !self.can_spawn(&e.name) {
+                    return Err(DataError::UnknownPrefab{ referenced_by: format!("spawn table for {}", key), name: e.name.clone() });
+                }
+                if let Some((from, to)) = e.hours {
+                    if from > 23 || to > 24 {
+                        return Err(DataError::BadSpawnTable{ key: key, problem: format!("{} has hours {}-{}", e.name, from, to) });
+                    }
+                }
+            }
         }
+        Ok(())
+    }
+
+    // copies inherited fields into every prefab, so nothing else has to know about parents
+    pub fn resolve_prefabs(&mut self) -> Result<(), DataError> {
+        resolve_list(&mut self.npcs)?;
+        resolve_list(&mut self.items)?;
+        resolve_list(&mut self.props)?;
+        Ok(())
     }
 
     pub fn load(&mut self, loaded: DataMaster) {
         //just copy everything over
         self.npcs = loaded.npcs;
         self.items = loaded.items;
+        self.props = loaded.props;
         self.map = loaded.map;
+        self.start_spawns = loaded.start_spawns;
+        self.spawn_tables = loaded.spawn_tables;
+        self.tiles = loaded.tiles;
+        self.glyphs = loaded.glyphs;
+        self.name_cultures = loaded.name_cultures;
+        self.scenarios = loaded.scenarios;
+        self.sources = loaded.sources;
+        self.files = loaded.files;
     }
 }
 
+// (file name, contents), base game first, then mods in the order they should apply
+pub fn parse_layers(files: &[(String, String)]) -> Result<DataMaster, DataLoadError> {
+    let mut data = DataMaster::empty();
+    let mut has_map = false;
+    for (file, ron) in files.iter() {
+        let layer : DataLayer = match ron::from_str(ron) {
+            Ok(layer) => layer,
+            Err(e) => {
+                return Err(DataLoadError{ file: file.to_string(), line: e.span.start.line, column: e.span.start.col, error: DataError::Parse(e.code.to_string()) });
+            }
+        };
+        has_map = has_map || layer.map.is_some();
+        data.files.push(file.to_string());
+        if let Err(error) = data.merge_layer(layer, file) {
+            return Err(located(error, file, ron));
+        }
+    }
+    let last = files.last().map_or("", |f| f.0.as_str());
+    if !has_map {
+        return Err(DataLoadError{ file: last.to_string(), line: 0, column: 0, error: DataError::MissingMap });
+    }
+
+    //after merging, so that mods can inherit from the base game's templates
+    let res = data.resolve_prefabs().and_then(|_| data.validate());
+    match res {
+        Ok(_) => Ok(data),
+        Err(error) => {
+            //look for it in the file it came from, or else the last one that mentions it
+            let file = match error.subject() {
+                Some((name, sections)) => data.source_of(name).map(|f| f.to_string()).or_else(|| {
+                    files.iter().rev().find(|f| locate(&f.1, sections, name, 0) != (0, 0)).map(|f| f.0.clone())
+                }).unwrap_or(last.to_string()),
+                None => last.to_string(),
+            };
+            let ron = files.iter().find(|f| f.0 == file).map_or("", |f| f.1.as_str());
+            Err(located(error, &file, ron))
+        }
+    }
+}
+
+fn located(error: DataError, file: &str, ron: &str) -> DataLoadError {
+    let (line, column) = match error.subject() {
+        Some((name, sections)) => {
+            //duplicates are reported where the second one is, if both are in this file
+            let twice = definitions(ron, sections, name).len() > 1;
+            let nth = if let (DataError::DuplicateName(_), true) = (&error, twice) { 1 } else { 0 };
+            locate(ron, sections, name, nth)
+        },
+        None => (0, 0),
+    };
+    DataLoadError{ file: file.to_string(), line: line, column: column, error: error }
+}
+
 //async loader based on https://rustwasm.github.io/docs/wasm-bindgen/examples/fetch.html
-// returning Universe as a workaround for https://github.com/rustwasm/wasm-bindgen/issues/1858
-pub async fn load_datafile(mut state: Universe) -> Universe {
+// base game first, then mods
+pub async fn load_datafiles(urls: &[String]) -> Result<DataMaster, DataLoadError> {
+    let mut files = Vec::new();
+    for url in urls {
+        let ron = fetch_text(url).await.map_err(|e| DataLoadError{ file: url.to_string(), line: 0, column: 0, error: DataError::Fetch(e) })?;
+        log!("Loaded from rust: {}", &format!("{:?}", ron));
+        files.push((url.to_string(), ron));
+    }
+
+    let data = parse_layers(&files)?;
+    //debug
+    for e in &data.npcs {
+        log!("{}", &format!("Ent from prefab: {} {:?} {:?} {:?} {:?}", e.name, e.renderable, e.ai, e.faction, e.combat));
+    }
+    for e in &data.items {
+        log!("{}", &format!("Item from prefab: {} {:?} {:?} {:?} {:?}", e.name, e.renderable, e.item, e.equippable, e.defense));
+    }
+    Ok(data)
+}
+
+async fn fetch_text(url: &str) -> Result<String, String> {
     let mut opts = RequestInit::new();
     opts.method("GET");
     opts.mode(RequestMode::Cors);
 
-    let url = "./data.ron";
-
-    let request = Request::new_with_str_and_init(&url, &opts).unwrap(); //no ? because we don't return Result
-
-    request
-        .headers();
-        //.set("Accept", "application/vnd.github.v3+json")?;
-        //.unwrap();
+    let request = Request::new_with_str_and_init(&url, &opts).map_err(|e| format!("{:?}", e))?;
 
-    let window = web_sys::window().unwrap();
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.unwrap(); //?;
+    let window = web_sys::window().ok_or("no window".to_string())?;
+    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.map_err(|e| format!("{:?}", e))?;
 
     // `resp_value` is a `Response` object.
-    assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().unwrap();
+    let resp: Response = resp_value.dyn_into().map_err(|e| format!("{:?}", e))?;
+    if !resp.ok() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
 
     // Convert this other `Promise` into a rust `Future`, and then to string
-    let ron = JsFuture::from(resp.text().unwrap()).await.unwrap().as_string().unwrap(); //?;
+    let text = JsFuture::from(resp.text().map_err(|e| format!("{:?}", e))?).await.map_err(|e| format!("{:?}", e))?;
+    text.as_string().ok_or("not text".to_string())
+}
 
-    log!("Loaded from rust: {}", &format!("{:?}", ron));
+///-----------------------------------------------------------------------------
+//hot reload, for tuning numbers without restarting
 
-    let data : DataMaster = ron::from_str(&ron).expect("malformed file");
-    //debug
-    for e in &data.npcs {
-        log!("{}", &format!("Ent from prefab: {} {:?} {:?} {:?} {:?}", e.name, e.renderable, e.ai, e.faction, e.combat));
+//fetching is async, so the result waits in PENDING_RELOAD until the next tick
+pub fn start_reload() {
+    let files = DATA.lock().unwrap().files.clone();
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(async move {
+        let res = load_datafiles(&files).await;
+        *PENDING_RELOAD.lock().unwrap() = Some(res);
+    });
+    #[cfg(not(target_arch = "wasm32"))]
+    log!("{}", &format!("Reloading {:?} only works in the browser", files));
+}
+
+//sets it if the prefab has it, takes it away if the prefab used to have it and doesn't anymore
+//leaves it alone if the prefab didn't change it, so whatever happened to it in game (spent money, a different conversation) stays
+fn update_component<T: hecs::Component + Clone + Serialize>(world: &mut World, e: Entity, old: &Option<T>, new: &Option<T>) {
+    if !changed(Some(old), new) {
+        return;
     }
-    for e in &data.items {
-        log!("{}", &format!("Item from prefab: {} {:?} {:?} {:?} {:?}", e.name, e.renderable, e.item, e.equippable, e.defense));
+    match new {
+        Some(c) => { world.insert_one(e, c.clone()); },
+        None => if old.is_some() { world.remove_one::<T>(e); },
     }
+}
 
+fn changed<T: Serialize>(old: Option<&T>, new: &T) -> bool {
+    match old {
+        Some(old) => serde_json::to_string(old).ok() != serde_json::to_string(new).ok(),
+        None => true,
+    }
+}
 
-    DATA.lock().unwrap().load(data);
-        
-    //log!("{}", &format!("{:?}", data));
-    //DATA.lock().unwrap() = data;
+impl Universe {
+    pub fn apply_pending_reload(&mut self) -> bool {
+        let pending = PENDING_RELOAD.lock().unwrap().take();
+        match pending {
+            Some(Ok(data)) => {
+                let count = self.reload_data(data);
+                self.game_message(&format!("{{gData reloaded, {} entities updated", count));
+                true
+            },
+            //the old data stays
+            Some(Err(e)) => {
+                self.game_message(&format!("{{rCould not reload {}", e));
+                false
+            },
+            None => false,
+        }
+    }
+
+    // swaps DATA and updates whatever was spawned from a prefab that changed
+    // a prefab is known by its name, so renaming one makes it a new prefab
+    // (equipment and NPCs' random names are only rolled on spawn, so those stay)
+    pub fn reload_data(&mut self, new: DataMaster) -> usize {
+        let mut count = 0;
+        {
+            let old = DATA.lock().unwrap();
+            let spawned : Vec<(Entity, String)> = self.ecs_world.query::<&SpawnedFrom>().iter().map(|(id, s)| (id, s.prefab.clone())).collect();
+            for (e, name) in spawned {
+                if let Some(np) = new.find_npc(&name) {
+                    let op = old.find_npc(&name);
+                    if changed(op, np) {
+                        self.update_npc(e, op, np, &new);
+                        count += 1;
+                    }
+                } else if let Some(ip) = new.find_item(&name) {
+                    let op = old.find_item(&name);
+                    if changed(op, ip) {
+                        self.update_item(e, op, ip, &new);
+                        count += 1;
+                    }
+                } else if let Some(pp) = new.find_prop(&name) {
+                    if changed(old.find_prop(&name), pp) {
+                        self.update_glyph(e, pp, &new);
+                        count += 1;
+                    }
+                } else {
+                    log!("{}", &format!("{} is no longer defined, leaving {:?} as it is", name, e));
+                }
+            }
+        }
+        self.set_tile_defs(&new.tiles);
+        DATA.lock().unwrap().load(new);
+        self.rebuild_fov();
+        self.rebuild_blocked();
+        return count;
+    }
+
+    fn update_glyph(&mut self, e: Entity, prefab: &dyn Prefab, data: &DataMaster) {
+        if let Ok(mut render) = self.ecs_world.get_mut::<Renderable>(e) {
+            render.glyph = data.glyph_id(prefab.renderable()).unwrap(); //validated
+        }
+    }
 
+    fn update_npc(&mut self, e: Entity, old: Option<&NPCPrefab>, new: &NPCPrefab, data: &DataMaster) {
+        self.update_glyph(e, new, data);
+        let w = &mut self.ecs_world;
+        update_component(w, e, &old.and_then(|o| o.ai), &new.ai);
+        update_component(w, e, &old.and_then(|o| o.faction), &new.faction);
+        update_component(w, e, &old.and_then(|o| o.vendor), &new.vendor);
+        update_component(w, e, &old.and_then(|o| o.conversation.clone()), &new.conversation);
+        update_component(w, e, &old.and_then(|o| o.attributes), &new.attributes);
+        update_component(w, e, &old.and_then(|o| o.money), &new.money);
 
-    state.game_start(&DATA.lock().unwrap());
-    //state.game_start(data);
-    //state.spawn_entities(data);
+        //damage taken so far carries over
+        let old_combat = old.and_then(|o| o.combat);
+        match (new.combat, w.get::<CombatStats>(e).ok().map(|c| *c)) {
+            (Some(mut combat), Some(current)) if changed(Some(&old_combat), &Some(combat)) => {
+                let damage = current.max_hp - current.hp;
+                combat.hp = std::cmp::max(1, combat.max_hp - damage);
+                w.insert_one(e, combat);
+            },
+            (Some(_), Some(_)) => {},
+            (combat, _) => update_component(w, e, &old_combat, &combat),
+        }
+        //so does energy
+        let energy = w.get::<Speed>(e).map_or(0, |sp| sp.energy);
+        update_component(w, e, &old.and_then(|o| o.speed).map(|s| Speed{speed: s, energy: energy}), &new.speed.map(|s| Speed{speed: s, energy: energy}));
+    }
 
-    return state
-}
\ No newline at end of file
+    fn update_item(&mut self, e: Entity, old: Option<&ItemPrefab>, new: &ItemPrefab, data: &DataMaster) {
+        self.update_glyph(e, new, data);
+        let w = &mut self.ecs_world;
+        //unlike NPCs, items are called what their prefab is called
+        if w.get::<String>(e).map_or(false, |name| *name != new.name) {
+            w.insert_one(e, new.name.clone());
+        }
+        update_component(w, e, &old.and_then(|o| o.item), &new.item);
+        update_component(w, e, &old.and_then(|o| o.equippable), &new.equippable);
+        update_component(w, e, &old.and_then(|o| o.defense), &new.defense);
+        update_component(w, e, &old.and_then(|o| o.melee), &new.melee);
+        update_component(w, e, &old.and_then(|o| o.consumable), &new.consumable);
+        update_component(w, e, &old.and_then(|o| o.heals), &new.heals);
+        update_component(w, e, &old.and_then(|o| o.food), &new.food);
+        update_component(w, e, &old.and_then(|o| o.quench), &new.quench);
+    }
+}
@@ -0,0 +1,149 @@
+//what happens in the game, as data - the message log, statistics and JS all listen to those
+use std::cell::RefCell;
+
+use serde::{Serialize, Deserialize};
+
+use super::Rolls;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+    AttackHit { attacker: String, target: String, by_player: bool, rolls: Vec<bool>, damage: i32 },
+    AttackMissed { attacker: String, target: String, by_player: bool, rolls: Vec<bool> },
+    ItemPickedUp { who: String, item: String },
+    ItemUsed { who: String, item: String },
+    Healed { who: String, amount: i32 },
+    ItemEaten { who: String, item: String },
+    ItemDrunk { who: String, item: String },
+    ItemEquipped { who: String, item: String },
+    ItemUnequipped { who: String, item: String },
+    ItemDropped { who: String, item: String },
+    EntityDied { name: String, by_player: bool },
+    PlayerDied,
+    Rested { who: String },
+    TimeAdvanced { turns: i64, time: String }, //time is HH:MM:SS
+    LevelChanged { level: usize, name: String },
+}
+
+impl GameEvent {
+    //what goes in the message log (using the markup from output.rs)
+    pub fn messages(&self) -> Vec<String> {
+        match self {
+            GameEvent::AttackHit { attacker, target, by_player, rolls, damage } => {
+                vec![kick_message(attacker, target, *by_player),
+                    roll_message(rolls),
+                    "Attack hits!".to_string(),
+                    format!("Dealt {{r{}}} damage", damage)]
+            },
+            GameEvent::AttackMissed { attacker, target, by_player, rolls } => {
+                vec![kick_message(attacker, target, *by_player),
+                    roll_message(rolls),
+                    "Attack missed!".to_string()]
+            },
+            GameEvent::ItemPickedUp { who, item } => vec![format!("{} picked up {}", who, item)],
+            GameEvent::ItemUsed { who, item } => vec![format!("{} used {}", who, item)],
+            GameEvent::Healed { who, amount } => vec![format!("{{g{} heals {} damage", who, amount)],
+            GameEvent::ItemEaten { who: _, item } => vec![format!("{{gYou eat the {}", item)],
+            GameEvent::ItemDrunk { who: _, item } => vec![format!("{{gYou drink the {}", item)],
+            GameEvent::ItemEquipped { who, item } => vec![format!("{{g{} equips {}", who, item)],
+            GameEvent::ItemUnequipped { who: _, item } => vec![format!("{{rYou unequip {}.", item)],
+            GameEvent::ItemDropped { who, item } => vec![format!("{} drops {}", who, item)],
+            GameEvent::EntityDied { name, .. } => vec![format!("{{grAI {} is dead", name)],
+            GameEvent::PlayerDied => vec!["{rYou are DEAD!".to_string()],
+            GameEvent::Rested { who: _ } => vec!["{gDone resting".to_string()],
+            GameEvent::TimeAdvanced { turns: _, time } => vec![format!("Time: {}", time)],
+            GameEvent::LevelChanged { level: _, name } => vec![format!("You take the stairs to the {}", name)],
+        }
+    }
+}
+
+fn kick_message(attacker: &str, target: &str, by_player: bool) -> String {
+    if by_player {
+        format!("{{gPlayer kicked the {}", target)
+    } else {
+        format!("{{rAI {} kicked at the player", attacker)
+    }
+}
+
+fn roll_message(rolls: &[bool]) -> String {
+    let sum = rolls.iter().filter(|&&b| b).count();
+    format!("Test: {} sum: {{g{}", Rolls(rolls.to_vec()), sum)
+}
+
+///-----------------------------------------------------------------------------
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Statistics {
+    pub attacks: u32,
+    pub hits: u32,
+    pub damage_dealt: i32,
+    pub damage_taken: i32,
+    pub kills: u32,
+    pub items_used: u32,
+}
+
+impl Statistics {
+    pub fn record(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::AttackHit { by_player, damage, .. } => {
+                if *by_player {
+                    self.attacks += 1;
+                    self.hits += 1;
+                    self.damage_dealt += damage;
+                } else {
+                    self.damage_taken += damage;
+                }
+            },
+            GameEvent::AttackMissed { by_player, .. } => {
+                if *by_player {
+                    self.attacks += 1;
+                }
+            },
+            GameEvent::EntityDied { by_player, .. } => {
+                if *by_player {
+                    self.kills += 1;
+                }
+            },
+            GameEvent::ItemUsed { .. } => self.items_used += 1,
+            _ => {},
+        }
+    }
+}
+
+///-----------------------------------------------------------------------------
+pub type Subscriber = Box<dyn FnMut(&GameEvent)>;
+
+// RefCells because events get published from inside ECS queries, where we only have &self (same as GameOutput)
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: RefCell<Vec<Subscriber>>,
+    //kept until JS picks them up
+    pending: RefCell<Vec<GameEvent>>,
+}
+
+//don't grow forever if nobody drains them
+const MAX_PENDING: usize = 1000;
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        Default::default()
+    }
+
+    pub fn subscribe(&self, subscriber: Subscriber) {
+        self.subscribers.borrow_mut().push(subscriber);
+    }
+
+    pub fn publish(&self, event: GameEvent) {
+        for s in self.subscribers.borrow_mut().iter_mut() {
+            s(&event);
+        }
+        let mut pending = self.pending.borrow_mut();
+        pending.push(event);
+        if pending.len() > MAX_PENDING {
+            let extra = pending.len() - MAX_PENDING;
+            pending.drain(0..extra);
+        }
+    }
+
+    pub fn drain(&self) -> Vec<GameEvent> {
+        self.pending.borrow_mut().drain(..).collect()
+    }
+}
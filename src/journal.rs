@@ -0,0 +1,104 @@
+//records everything the player does, so that a session can be replayed exactly
+use serde::{Serialize, Deserialize};
+
+use super::log;
+use super::{Universe, Command, WaitType};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JournalEntry {
+    Process(Command),
+    MovePlayer(i32, i32),
+    AstarPath(i32, i32),
+    AdvanceAutomove,
+    Wait(WaitType),
+    UseItem(u64),
+    DropItem(u64),
+    // UI actions (vendor, character creation, debug console) change the game, too
+    GiveItem(String),
+    ChangeMoney(f32),
+    SetPlayerStats(Vec<i32>),
+    ConsoleInput(String),
+    LoadSave(String),
+    LoadSaveCompact(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    pub seed: u64,
+    #[serde(default)] //older journals were all the same start
+    pub scenario: String,
+    pub entries: Vec<JournalEntry>,
+    //process() calls the other recorded functions, only the command itself should end up in the journal
+    //a counter because pauses nest (console -> queued commands -> move_player)
+    #[serde(skip)]
+    paused: u32,
+}
+
+impl Journal {
+    pub fn new(seed: u64) -> Journal {
+        Journal{ seed: seed, scenario: String::new(), entries: Vec::new(), paused: 0 }
+    }
+
+    pub fn record(&mut self, entry: JournalEntry) {
+        if self.paused == 0 {
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused += 1;
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused > 0 {
+            self.paused -= 1;
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string(self).unwrap_or_default();
+    }
+
+    pub fn from_json(data: &str) -> Option<Journal> {
+        match serde_json::from_str(data) {
+            Ok(journal) => Some(journal),
+            Err(e) => {
+                log!("{}", &format!("Malformed journal: {}", e));
+                None
+            }
+        }
+    }
+}
+
+impl Universe {
+    // the universe has to be a fresh game started with the journal's seed
+    pub fn replay(&mut self, journal: &Journal) -> bool {
+        if journal.seed != self.seed {
+            log!("{}", &format!("Journal seed {} does not match game seed {}", journal.seed, self.seed));
+            return false;
+        }
+        if !journal.scenario.is_empty() && journal.scenario != self.journal.scenario {
+            log!("{}", &format!("Journal scenario {} does not match game scenario {}", journal.scenario, self.journal.scenario));
+            return false;
+        }
+
+        for entry in journal.entries.iter() {
+            match entry.clone() {
+                JournalEntry::Process(cmd) => self.process(Some(cmd)),
+                JournalEntry::MovePlayer(x, y) => self.move_player(x, y),
+                JournalEntry::AstarPath(x, y) => self.astar_path(x, y),
+                JournalEntry::AdvanceAutomove => self.advance_automove(),
+                JournalEntry::Wait(opt) => self.wait(opt),
+                JournalEntry::UseItem(id) => self.use_item_ext(id),
+                JournalEntry::DropItem(id) => self.drop_item_ext(id),
+                JournalEntry::GiveItem(name) => self.give_item(name),
+                JournalEntry::ChangeMoney(val) => self.change_money(val),
+                JournalEntry::SetPlayerStats(stats) => self.set_player_stats(stats),
+                JournalEntry::ConsoleInput(input) => self.console_input(input),
+                JournalEntry::LoadSave(data) => { self.load_save(data); },
+                JournalEntry::LoadSaveCompact(data) => { self.load_save_compact(data); },
+            }
+        }
+        return true;
+    }
+}
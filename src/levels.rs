@@ -0,0 +1,113 @@
+//the world is a list of maps - the town is level 0, then the cellars, upper floors and sewers linked to it by stairs
+//the player's level always lives in Universe.map, because FOV, drawing and the player's pathing only look there,
+//its slot in Universe.levels holds a dummy until the player leaves
+use super::{Universe, GameEvent, Level, Player, Path};
+use super::map::{Map, Exit, TileDef};
+
+use hecs::Entity;
+
+//entities without a Level are in the town
+pub fn on_level(lvl: Option<&Level>, level: usize) -> bool {
+    return lvl.map_or(0, |l| l.id) == level;
+}
+
+impl Universe {
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn level_map(&self, level: usize) -> &Map {
+        if level == self.level { &self.map } else { &self.levels[level] }
+    }
+
+    pub fn level_map_mut(&mut self, level: usize) -> &mut Map {
+        if level == self.level { &mut self.map } else { &mut self.levels[level] }
+    }
+
+    pub fn level_name(&self, level: usize) -> String {
+        let name = &self.level_map(level).name;
+        if name.is_empty() { "town".to_string() } else { name.clone() }
+    }
+
+    pub fn entity_level(&self, e: Entity) -> usize {
+        return self.ecs_world.get::<Level>(e).map_or(0, |l| l.id);
+    }
+
+    // swaps the level in as self.map for the duration, for code that only knows about self.map (e.g. the AI)
+    pub fn with_level<F: FnOnce(&mut Universe)>(&mut self, level: usize, f: F) {
+        if level == self.level {
+            f(self);
+            return;
+        }
+        std::mem::swap(&mut self.map, &mut self.levels[level]);
+        f(self);
+        std::mem::swap(&mut self.map, &mut self.levels[level]);
+    }
+
+    //a copy of every level, for saving
+    pub fn all_levels(&self) -> Vec<Map> {
+        return (0..self.level_count()).map(|l| self.level_map(l).clone()).collect();
+    }
+
+    //replaces the whole world, from a new game or a save
+    pub fn set_levels(&mut self, mut levels: Vec<Map>, level: usize) {
+        self.map = std::mem::replace(&mut levels[level], Map::new(0,0));
+        self.levels = levels;
+        self.level = level;
+    }
+
+    pub fn set_tile_defs(&mut self, defs: &[TileDef]) {
+        for l in 0..self.level_count() {
+            self.level_map_mut(l).set_tile_defs(defs);
+        }
+    }
+
+    //takes the player through the exit, everyone else stays where they are
+    //false (and a message) if there's no room on the other end
+    pub fn change_level(&mut self, exit: Exit) -> bool {
+        let dest = match arrival(self.level_map(exit.level), exit.dest) {
+            Some(idx) => idx,
+            None => {
+                self.game_message("{rThere's a crowd on the other side, you can't get through.");
+                return false;
+            }
+        };
+        self.map.clear_tile_blocked(self.player_position);
+        let old = self.level;
+        std::mem::swap(&mut self.map, &mut self.levels[old]);
+        std::mem::swap(&mut self.map, &mut self.levels[exit.level]);
+        self.level = exit.level;
+        self.player_position = dest;
+        self.map.set_tile_blocked(self.player_position);
+
+        //an automove path is on the old level
+        let player = self.ecs_world.query::<&Player>().iter().next().map(|(id, _)| id);
+        if let Some(entity) = player {
+            self.ecs_world.remove_one::<Path>(entity);
+        }
+
+        self.rebuild_fov();
+        //reveal tiles
+        for (idx, b) in self.fov_data.fov.iter().enumerate() {
+            if *b {
+                self.map.revealed_tiles[idx] = true;
+            }
+        }
+        self.emit(GameEvent::LevelChanged{ level: exit.level, name: self.level_name(exit.level) });
+        return true;
+    }
+}
+
+//the other end of the stairs, or next to it if someone's standing there
+fn arrival(map: &Map, dest: usize) -> Option<usize> {
+    if !map.is_tile_blocked(dest) {
+        return Some(dest);
+    }
+    let (x, y) = map.idx_xy(dest);
+    for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)].iter() {
+        if map.is_tile_walkable(x + dx, y + dy) && !map.is_tile_blocked(map.xy_idx(x + dx, y + dy)) {
+            return Some(map.xy_idx(x + dx, y + dy));
+        }
+    }
+    return None;
+}
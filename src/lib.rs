@@ -7,9 +7,6 @@ extern crate wasm_bindgen;
 
 use wasm_bindgen::prelude::*;
 
-//the websys bindings uses it
-use wasm_bindgen::JsCast; // for dyn_into
-
 //better panics
 extern crate console_error_panic_hook;
 use std::panic;
@@ -19,12 +16,17 @@ use hecs::World;
 use hecs::Entity;
 
 //RNG
-//use rand::Rng;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+// one seeded generator for everything, so that a seed reproduces the whole game
+pub type GameRng = Xoshiro256PlusPlus;
 
 //save/load
 use serde::{Serialize, Deserialize};
 
 use std::fmt;
+use std::rc::Rc;
+use std::cell::RefCell;
 
 //time
 use chrono::{NaiveTime, Timelike, Duration};
@@ -49,6 +51,7 @@ mod rect;
 use rect::*;
 
 mod data_loader;
+use data_loader::DATA;
 
 mod map_builders;
 
@@ -58,6 +61,30 @@ mod ai;
 
 mod saveload;
 
+mod save_slots;
+use save_slots::*;
+
+mod levels;
+use levels::*;
+
+mod output;
+use output::*;
+
+mod journal;
+use journal::*;
+
+mod command_queue;
+use command_queue::*;
+
+mod events;
+use events::*;
+
+mod message_log;
+use message_log::*;
+
+#[cfg(test)]
+mod tests;
+
 
 //lisp-y
 //mod lispy;
@@ -67,120 +94,21 @@ mod saveload;
 extern crate lazy_static;
 
 // A macro to provide `println!(..)`-style syntax for `console.log` logging.
+#[cfg(target_arch = "wasm32")]
 #[macro_export]
 macro_rules! log {
-    ( $( $t:tt )* ) => {
+    ( $( $t:tt )* ) => {{
         web_sys::console::log_1(&format!( $( $t )* ).into());
-    }
+    }}
 }
 
-//TODO: shuffle all or most of this to JS because Rust is clunky when it comes to DOM... :/
-//using web_sys here because I am not too sure on how to pass strings to custom JS
-pub fn game_message(string: &str)
-{
-    //convert
-    let mut string = string.to_string();
-    let window = web_sys::window().expect("global window does not exists");    
-    let document = window.document().expect("expecting a document on window");
-    
-    let messages = document.get_element_by_id("messages").unwrap();
-
-    let log_history = document.get_element_by_id("log-history").unwrap();
-
-    let line = document.create_element("div").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap(); //dyn_into for style() to work
-
-    //apply CSS to whole line
-    if string.starts_with("{r"){
-        //strip tag
-        string = string.trim_start_matches("{r").to_string();
-        line.style().set_property("color", "rgb(255,0,0)");
-    }
-    if string.starts_with("{gr"){
-        //strip tag
-        string = string.trim_start_matches("{gr").to_string();
-        line.style().set_property("color", "rgb(127,127,127)");
-    }
-    if string.starts_with("{g"){
-        //strip tag
-        string = string.trim_start_matches("{g").to_string();
-        line.style().set_property("color", "rgb(0,255,0)");
-    }
-
-    //detect in-line styles
-    if string.contains("{r") {
-        //parse style
-        string = string.replace("{r", "<span style=\"color:rgb(255,0,0)\">");
-        string = string.replace("}", "</span>");
-    }
-    if string.contains("{gr") {
-        //parse style
-        string = string.replace("{gr", "<span style=\"color:rgb(127,127,127)\">");
-        string = string.replace("}", "</span>");
-    }
-    if string.contains("{g") {
-        //parse style
-        string = string.replace("{g", "<span style=\"color:rgb(0,255,0)\">");
-        string = string.replace("}", "</span>");
-    }
-    if string.contains("{c"){
-        //parse style
-        string = string.replace("{c", "<span style=\"color:rgb(0,255,255)\">");
-        string = string.replace("}", "</span>");
-    }
-    if string.contains("{y"){
-        //parse style
-        string = string.replace("{y", "<span style=\"color:rgb(255,255,0)\">");
-        string = string.replace("}", "</span>");
-    }
-
-    if string.contains("🇪 🇸"){
-        //parse country flag
-        string = string.replace("🇪 🇸", "<img src=\"./es.svg\" alt=\"\" style=\"height:14px\">");
-    }
-
-
-    line.set_inner_html(&string); //wants &str
-    messages.append_child(&line).unwrap(); //implicitly converts to Node
-
-    //clone to place a copy
-    let ln = line.clone_node().unwrap().dyn_into::<web_sys::HtmlElement>().unwrap(); //Rust version for some reason doesn't have the deep parameter?
-    ln.set_inner_html(&string); //because clone_node doesn't do it for some reason
-    log_history.append_child(&ln).unwrap();
-
-    //axe the first if more than 5
-    while messages.child_element_count() > 5 {
-        messages.remove_child(&messages.first_element_child().unwrap()); //implicit conversion
-    }
-}	
-
-pub fn game_describe(string: &str) {
-    //convert
-    //let mut string = string.to_string();
-    let window = web_sys::window().expect("global window does not exists");    
-    let document = window.document().expect("expecting a document on window");
-
-    let desc = document.get_element_by_id("game-desc").unwrap();
-    //let line = document.create_element("div").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap(); //dyn_into for style() to work
-
-    desc.set_inner_html(string);
-}
-
-
-#[wasm_bindgen]
-#[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub enum RenderableGlyph {
-    Thug = 0,
-    Knife = 1,
-    Medkit = 2,
-    Barkeep = 3,
-    Table = 4,
-    Chair = 5,
-    Boots = 6,
-    Jacket = 7,
-    Jeans = 8,
-    Patron = 9,
-    Bed = 10
+// there is no console outside of the browser, so just print
+#[cfg(not(target_arch = "wasm32"))]
+#[macro_export]
+macro_rules! log {
+    ( $( $t:tt )* ) => {{
+        println!( $( $t )* );
+    }}
 }
 
 #[repr(u8)]
@@ -201,15 +129,40 @@ pub struct Renderable {
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct GameState{
     pub turns: i64, //to fit chrono
+    #[serde(default)] //older saves don't have it
+    pub seed: u64,
+    #[serde(default = "default_start_time")] //older saves all started at 08:00
+    pub start_time: i64, //seconds after midnight, from the scenario
 }
 
+fn default_start_time() -> i64 { 8 * 3600 }
+
+impl GameState {
+    //time of day, wraps past midnight
+    pub fn clock(&self) -> NaiveTime {
+        let start = NaiveTime::from_num_seconds_from_midnight_opt(self.start_time as u32, 0).unwrap();
+        //t is a tuple (NaiveTime, i64)
+        return start.overflowing_add_signed(Duration::seconds(self.turns)).0;
+    }
+}
+
+//survives save/load, unlike Entity ids - saves refer to entities by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PersistentId(pub u64);
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Player{}
+//which map the entity is on, see levels.rs (entities without it are in the town)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub id: usize
+}
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Needs{
     pub hunger: i32,
     pub thirst: i32,
 }
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Path{
     pub steps: Vec<usize> // see astar line 43
 }
@@ -230,6 +183,20 @@ pub struct Attributes {
     pub charisma : Attribute,
 }
 
+//energy is gained every second (turn), actions spend it
+//entities without it act every second
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Speed {
+    pub speed: i32, //energy per second, 100 is normal
+    pub energy: i32, //can act when >= 0
+}
+
+//action costs in energy, 100 is a second at normal speed
+pub const COST_WALK: i32 = 100;
+pub const COST_ATTACK: i32 = 150;
+pub const COST_MEDKIT: i32 = 500;
+pub const COST_DEFAULT: i32 = 100; //talking, waiting, using other items
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct AI {
     //pub state: i32;
@@ -261,6 +228,7 @@ pub struct Vendor {
     //pub categories : Vec<String>
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NPCName {
     pub name: String
 }
@@ -274,7 +242,7 @@ pub struct Item{}
 pub struct InBackpack{}
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Consumable{} //in the sense that it is limited use only
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ProvidesHealing {
     pub heal_amount : i32
 }
@@ -291,7 +259,18 @@ pub struct WantsToUseItem {
 pub struct WantsToDropItem {
     pub item : Entity
 }
+//who dealt the last hit, for whoever dies of it
+pub struct LastHitBy {
+    pub player : bool
+}
+//which prefab it was spawned from, so that reloaded data can update it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnedFrom {
+    pub prefab: String
+}
+
 // tells the engine to nuke us
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ToRemove {pub yes: bool} //bool is temporary while we can't modify entities when iterating
 
 #[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
@@ -302,10 +281,10 @@ pub struct Equippable {
 }
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Equipped {
-    pub owner : u64, //because Entity cannot be serialized by serde
+    pub owner : u64, //Entity bits, because Entity cannot be serialized by serde (saves store the owner's PersistentId instead)
     pub slot : EquipmentSlot
 }
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct MeleeBonus {
     pub bonus : i32
 }
@@ -315,6 +294,7 @@ pub struct DefenseBonus {
     pub bonus : f32
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Conversation {
     pub text: String,
     pub answers: Vec<String>
@@ -350,7 +330,7 @@ impl fmt::Display for Rolls {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum WaitType {
     Minutes5,
     Minutes30,
@@ -360,12 +340,8 @@ pub enum WaitType {
 }
 
 
-//input
-//for input that does not come from JS side (e.g. actions after conversation)
-pub static mut GLOBAL_INPUT: Option<Command> = None;
-
 #[wasm_bindgen]
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum Command {
     None, //dummy, unfortunately we can't use -1 in wasm_bindgen...
     MoveLeft,
@@ -382,45 +358,57 @@ pub enum Command {
 
 #[wasm_bindgen]
 pub struct Universe {
-    map: Map,
+    map: Map, //the level the player is on
     player_position: usize,
+    levels: Vec<Map>, //all of them, the player's one is swapped out into map
+    level: usize,
     fov: FovRecursiveShadowCasting,
     fov_data: MapData,
     ecs_world: World,
+    output: Box<dyn GameOutput>,
+    seed: u64,
+    rng: GameRng,
+    journal: Journal,
+    commands: CommandQueue,
+    command_results: Vec<CommandReport>,
+    events: EventBus,
+    stats: Rc<RefCell<Statistics>>,
+    message_log: RefCell<MessageLog>, //RefCell because messages come from places that only have &self
+    next_persistent_id: u64,
+    storage: Box<dyn SaveStorage>,
+    save_format: saveload::SaveFormat, //what save slots are written as
+    autosave: Rc<RefCell<Autosave>>, //RefCell because it's checked from calendar_time, which only has &self; Rc because it listens to events
+    data_error: Option<String>, //why the data file didn't load
+    scenario: Option<String>, //picked by the player, the data file's first one otherwise
 }
-//can't store rng here because of wasm_bindgen
 
 
 /// Public methods, exported to JavaScript.
 #[wasm_bindgen]
 // returning Universe as a workaround for https://github.com/rustwasm/wasm-bindgen/issues/1858
-pub async fn load_datafile_ex(mut state: Universe) -> Universe {
-    return data_loader::load_datafile(state).await;
+// files: the base data file, then any mods, in the order they apply
+pub async fn load_datafile_ex(mut state: Universe, files: Vec<String>) -> Universe {
+    match data_loader::load_datafiles(&files).await {
+        Ok(data) => {
+            DATA.lock().unwrap().load(data);
+            state.game_start(&DATA.lock().unwrap());
+        },
+        //the game doesn't start, JS checks data_error()
+        Err(e) => state.report_data_error(&e),
+    }
+    return state;
 }
 
 
 #[wasm_bindgen]
 impl Universe {
     pub fn new() -> Universe {
-        let mut state = Universe{
-            map: Map::new(2,2), //dummy
-            player_position: 0, //dummy
-            fov: FovRecursiveShadowCasting::new(),
-            fov_data: MapData::new(2,2), //dummy
-            ecs_world: World::new(),
-        };
-    
-        state.player_position = state.map.xy_idx(1,1); //default
-
-        //state.spawn_entities();
-
-        log!("We have a universe");
-
         //lispy test
         //parse_script();
 
-        // We'll return the state with the short-hand
-        state
+        let mut state = Universe::with_output(Box::new(WebOutput{}));
+        state.storage = Box::new(WebStorage{});
+        return state;
     }
 
 
@@ -430,7 +418,37 @@ impl Universe {
         let current_position = self.map.idx_xy(self.player_position);
         self.text_description(self.player_position, current_position.0, current_position.1);
         //greet the player
-        game_message(&format!("{{cWelcome to Neon Twilight!"));
+        let intro = DATA.lock().unwrap().find_scenario(self.scenario.as_deref()).map(|s| s.intro.clone()).unwrap_or_default();
+        if !intro.is_empty() {
+            self.game_message(&intro);
+        }
+    }
+
+    // like the seed, has to be picked before the data file is loaded
+    pub fn set_scenario(&mut self, name: String) {
+        self.scenario = Some(name);
+    }
+
+    //the one the game started with
+    pub fn scenario(&self) -> String {
+        return self.scenario.clone().unwrap_or_default();
+    }
+
+    //JSON list of scenario names and descriptions, for a new game menu
+    pub fn scenarios(&self) -> String {
+        let list : Vec<serde_json::Value> = DATA.lock().unwrap().scenarios.iter()
+            .map(|s| serde_json::json!({ "name": s.name, "description": s.description })).collect();
+        return serde_json::to_string(&list).unwrap_or_default();
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // has to be called before the data file is loaded, because that starts the game
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = GameRng::seed_from_u64(seed);
     }
 
     pub fn width(&self) -> u32 {
@@ -445,6 +463,11 @@ impl Universe {
         self.map.tiles.clone()
     }
 
+    //JS fetches the tiles again when this changes
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
 
     pub fn player(&self) -> Vec<i32> {
         let pos = self.map.idx_xy(self.player_position);
@@ -473,10 +496,11 @@ impl Universe {
         // based on https://aimlesslygoingforward.com/blog/2017/12/25/dose-response-ported-to-webassembly/ 
         let mut js_drawn = Vec::new();
 
-        let mut data = self.ecs_world.query::<(&Point, &Renderable)>()
+        let mut data = self.ecs_world.query::<(&Point, &Renderable, Option<&Level>)>()
         .without::<InBackpack>().without::<Equipped>() //no ref/pointer here!
         .iter()
-        .map(|(e, (&p, &r))| (e, p, r)) // Copy out of the world
+        .filter(|(_, (_, _, lvl))| on_level(*lvl, self.level))
+        .map(|(e, (&p, &r, _))| (e, p, r)) // Copy out of the world
         .collect::<Vec<_>>();
 
         //sort by render order
@@ -515,57 +539,43 @@ impl Universe {
 
     pub fn console_input(&mut self, input:String) {
         log!("Rust console input: {}", input);
-        
+        self.journal.record(JournalEntry::ConsoleInput(input.clone()));
+        //anything the console does gets redone by replaying the input
+        self.journal.pause();
         self.debug_console_core(input);
+        self.journal.resume();
     }
 
 
-    pub fn process(&mut self, mut input: Option<Command>) {
-        //new: handle other sorts of input sources
-        //UGLY but I don't know a better way to do it, it's based on bracketlib input handling
-        unsafe {
-            if GLOBAL_INPUT.is_some() {
-                input = GLOBAL_INPUT;
-                log!("Global input: {:?}", input);
-            }
-
+    // queues the command (if any) and processes everything that's queued, in order
+    pub fn process(&mut self, input: Option<Command>) {
+        if let Some(cmd) = input {
+            self.commands.push(cmd);
         }
-        // New: handle keyboard inputs.
-        match input {
-            None => {}, // Nothing happened
-            Some(input) => {
-                // A key is pressed or held
-                match input {
-                    // We're matching a command from the host
-                    // and applying movement via the move_player function.
-
-                    // Cursors
-                    Command::MoveUp => self.move_player(0, -1),
-                    Command::MoveDown => self.move_player(0, 1),
-                    Command::MoveLeft => self.move_player(-1, 0),
-                    Command::MoveRight => self.move_player(1, 0),
-
-                    //others
-                    Command::GetItem => self.get_item(),
-                    Command::Rest => self.rest(),
-                    
-                    //Command::Wait => self.wait(),
-                    //save/load
-                    //Command::SaveGame => self.save_game(),
 
-                    _ => {} // Ignore all the other possibilities
-                }
+        //only report on this call
+        self.command_results.clear();
+        while let Some(cmd) = self.commands.pop() {
+            let result = self.process_command(cmd);
+            if result != CommandResult::Consumed {
+                log!("{}", &format!("Command {:?} rejected: {:?}", cmd, result));
             }
+            self.command_results.push(CommandReport{ command: cmd, result: result });
         }
+    }
 
-        //clear
-        unsafe {
-            GLOBAL_INPUT = None;
-        }
+    //for commands that should wait for the next process() call
+    pub fn queue_command(&mut self, cmd: Command) {
+        self.commands.push(cmd);
+    }
 
+    //JSON list of what happened to commands in the last process() call
+    pub fn command_results(&self) -> String {
+        return serde_json::to_string(&self.command_results).unwrap_or_default();
     }
 
     pub fn astar_path(&mut self, x:i32, y:i32) {
+        self.journal.record(JournalEntry::AstarPath(x, y));
         if self.is_player_dead() {
             return;
         }
@@ -590,6 +600,7 @@ impl Universe {
     // and if it is a floor - move the player there.
     pub fn move_player(&mut self, delta_x: i32, delta_y: i32) {
         //log!("Move player x {} y {}", delta_x, delta_y);
+        self.journal.record(JournalEntry::MovePlayer(delta_x, delta_y));
 
         if self.is_player_dead() {
             return;
@@ -606,117 +617,35 @@ impl Universe {
                     let fact = self.ecs_world.get::<Faction>(entity).unwrap().typ;
                     if fact == FactionType::Enemy {
                             //this assumes the blocker has a name!
-                            game_message(&format!("{{gPlayer kicked the {}", self.ecs_world.get::<String>(entity).unwrap().to_string()));
-                            self.attack(&entity);
+                            //get player entity
+                            let mut play: Option<Entity> = None;
+                            for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
+                                play = Some(id);
+                            }
+                            self.attack(&play.unwrap(), &entity);
+                            //enemy turn
+                            self.end_turn(COST_ATTACK);
                     } else if fact == FactionType::Townsfolk {
                         if self.ecs_world.get::<Vendor>(entity).is_ok() {
                             //game_message(&format!("You talk to the vendor"));
                             //GUI
-                            let window = web_sys::window().expect("global window does not exists");    
-                            let document = window.document().expect("expecting a document on window");                        
-                            let vendor = document.get_element_by_id("vendor").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
-                            let list = vendor.class_list().toggle("visible");
+                            self.output.open_vendor();
                         } 
                         else if self.ecs_world.get::<Conversation>(entity).is_ok() {
                             let conv = self.ecs_world.get::<Conversation>(entity).unwrap();
-                            //display convo
-                            let window = web_sys::window().expect("global window does not exists");    
-                            let document = window.document().expect("expecting a document on window");                        
-                            let view = document.get_element_by_id("conversation").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
-                            
-                            //the img is there to mark language being spoken by NPC
-                            let text = &format!("<div> <img src=\"./es.svg\" alt=\"\" style=\"height:14px\"> {} </div>", conv.text);
-                            let mut replies = "".to_string();
-                            for (i, a) in conv.answers.iter().enumerate() {
-                                let tmp = format!("<button id=conv-id-{}>{}</button> <span style=\"color:rgb(0,255,0)\"> {} </span>", i, i, a);
-                                replies = format!("{} \n {}", replies, tmp);
-                            } 
-                            //append a question for their name
-                            if self.ecs_world.get::<NPCName>(entity).is_ok() {
-                                let tmp = format!("<button id=conv-id-{}>{}</button> <span style=\"color:rgb(0,255,0)\"> Cual es tu nombre?</span>", conv.answers.len(), conv.answers.len());
-                                replies = format!("{} \n {}", replies, tmp);
-                            }
-
-                            // mark language for the player, too
-                            view.set_inner_html(&format!("{} \n <img src=\"./es.svg\" alt=\"\" style=\"height:14px\"> {}", text, replies));
-
-
-                            //basic interactivity
-                            for (i,a) in conv.answers.iter().enumerate() {
-                                //closure
-                                //needs move due to i being used
-                                let click_handle =  Closure::wrap(Box::new(move || {
-                                    //log!("Test click handler");
-                                    log!("Clicked button for answer id {}", i);
-                                    
-                                    //close the menu for now
-                                    //get the damned thing by ourselves to avoid 'value moved'
-                                    let window = web_sys::window().expect("global window does not exists");    
-                                    let document = window.document().expect("expecting a document on window");  
-                                    let view = document.get_element_by_id("conversation").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
-                                    view.class_list().toggle("visible");
-
-                                    //test
-                                    // unsafe {
-                                    //     GLOBAL_INPUT = Some(Command::MoveLeft);
-                                    //     log!("Command to move left issued");
-                                    // }
-                                    
-                                }) as Box<dyn FnMut()>);
-
-                                let id = &format!("conv-id-{}", i);
-                                let but = document.get_element_by_id(id).unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
-                                but.set_onclick(Some(click_handle.as_ref().unchecked_ref()));
-
-                                //avoid memleak on Rust side
-                                click_handle.forget();
-                            }
-
-                            // handle asking their name
-                            if self.ecs_world.get::<NPCName>(entity).is_ok() {
-                                //workaround for the fact that we need owned (static) variable
-                                let name = String::from(&self.ecs_world.get::<NPCName>(entity).unwrap().name);
-                                //log!("{}", &format!("NPC name: {}", name));
-                                // https://rustwasm.github.io/wasm-bindgen/api/wasm_bindgen/closure/struct.Closure.html
-                                //It must be 'static, aka no stack references (use the move keyword).
-                                let click_handle_b = Closure::wrap(Box::new(move || {
-                                    log!("{}", &format!("Clicked name question, {}", name));
-
-                                    //redraw the window
-                                    //get the damned thing by ourselves to avoid 'value moved'
-                                    let window = web_sys::window().expect("global window does not exists");    
-                                    let document = window.document().expect("expecting a document on window");
-                                    let view = document.get_element_by_id("conversation").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
-                                
-                                    //the img is there to mark language being spoken by NPC
-                                    let text = &format!("<div> <img src=\"./es.svg\" alt=\"\" style=\"height:14px\"> Me llamo {} </div>", name);
-                                    view.set_inner_html(text);
-
-                                }) as Box<dyn FnMut()>);
-                                
-                                
-                                let id = &format!("conv-id-{}", conv.answers.len());
-                                let but = document.get_element_by_id(id).unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
-                                // uses `as_ref()` to get a `JsValue`
-                                // from our `Closure` which is then converted to a `&Function`
-                                // using the `JsCast::unchecked_ref` function.
-                                but.set_onclick(Some(click_handle_b.as_ref().unchecked_ref()));
-
-
-                                //avoid memleak on Rust side
-                                click_handle_b.forget();
-                            }
-
-                            let list = view.class_list().toggle("visible");
-                           
+                            //workaround for the fact that the UI needs an owned (static) variable
+                            let name = match self.ecs_world.get::<NPCName>(entity) {
+                                Ok(npc_name) => Some(npc_name.name.to_string()),
+                                Err(_) => None,
+                            };
+                            self.output.open_conversation(&conv, name);
                         }
                         else {
-                            game_message(&format!("The man says 🇪 🇸: hola!"));
+                            self.game_message(&format!("The man says 🇪 🇸: hola!"));
                         }
+                        //enemy turn
+                        self.end_turn(COST_DEFAULT);
                     }
-
-                    //enemy turn
-                    self.end_turn();
                 },
                 None => {
                     self.map.clear_tile_blocked(self.player_position);
@@ -726,6 +655,17 @@ impl Universe {
                     //mark player tile as unwalkable for AI/pathing
                     self.map.set_tile_blocked(new_idx);
 
+                    //stairs take you straight to the other level
+                    //(if the way is blocked, they just stand on them)
+                    if let Some(exit) = self.map.exit_at(new_idx) {
+                        if self.change_level(exit) {
+                            let pos = self.map.idx_xy(self.player_position);
+                            self.text_description(self.player_position, pos.0, pos.1);
+                            self.end_turn(COST_WALK);
+                            return;
+                        }
+                    }
+
                     //refresh fov
                     self.fov_data.clear_fov(); // compute_fov does not clear the existing fov
                     self.fov.compute_fov(&mut self.fov_data, new_position.0 as usize, new_position.1 as usize, 6, true);
@@ -739,7 +679,7 @@ impl Universe {
                     self.text_description(new_idx, new_position.0, new_position.1);
 
                     //enemy turn
-                    self.end_turn();
+                    self.end_turn((COST_WALK as f32 * self.map.move_cost(new_idx)) as i32);
                 }
             }
                  
@@ -806,6 +746,7 @@ impl Universe {
     }
 
     pub fn advance_automove(&mut self) {
+        self.journal.record(JournalEntry::AdvanceAutomove);
         //get player entity
         let mut play: Option<Entity> = None;
         for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
@@ -827,29 +768,19 @@ impl Universe {
         let new_position = (new_x, new_y);
 
 
-        let area_desc = "This area appears to be a town that hugs a forest.";
-        let mut terrain_desc = "";
-        if (self.map.tiles[new_idx] == Cell::Grass as u8){
-            terrain_desc = "You feel the grass under your feet.";
-        }
-        else if (self.map.tiles[new_idx] == Cell::Floor as u8) {
-            terrain_desc = " You walk on paved ground of the town.";
-        }
-        else if (self.map.tiles[new_idx] == Cell::FloorIndoor as u8) {
-            terrain_desc = " You entered one of the buildings.\n";
-        }
+        let area_desc = if self.map.name.is_empty() { "This area appears to be a town that hugs a forest.".to_string() } else { format!("You are in the {}.", self.map.name) };
+        let terrain_desc = self.map.tile_def(new_idx).map_or("", |d| d.description.as_str());
 
         //describe the doors/walls in sight
         let mut other_desc = "".to_string();
         let mut has_walls = false;
 
         let walls = self.map.revealed_tiles.iter().enumerate()
-            .filter(|x| if *x.1 && (self.map.tiles[x.0] == Cell::Wall as u8 || self.map.tiles[x.0] == Cell::Mountain as u8)
-                { return true } else { return false } )
+            .filter(|x| if *x.1 && !self.map.is_tile_transparent(x.0) { return true } else { return false } )
             .collect::<Vec<(usize, &bool)>>();
         
         let doors = self.map.revealed_tiles.iter().enumerate()
-        .filter(|x| if *x.1 && self.map.tiles[x.0] == Cell::Door as u8 { return true } else { return false } )
+        .filter(|x| if *x.1 && self.map.is_tile(x.0, "door") { return true } else { return false } )
         .collect::<Vec<(usize, &bool)>>();
 
         //TODO: instead of describing individual tiles, figure out how to describe as "a wall 9 N 5 wide"
@@ -919,7 +850,7 @@ impl Universe {
             ent_desc = format!("{} {}", ent_desc, tmp);
         }
 
-        game_describe(&format!("{} {} {}\n {}", area_desc, terrain_desc, other_desc, ent_desc));
+        self.game_describe(&format!("{} {} {}\n {}", area_desc, terrain_desc, other_desc, ent_desc));
         
     }
 
@@ -931,23 +862,23 @@ impl Universe {
         match item {
             Some(entity) => {
                 //this assumes the blocker has a name!
-                game_message(&format!("Player picked up {}", self.ecs_world.get::<String>(entity).unwrap().to_string()));
+                self.emit(GameEvent::ItemPickedUp{ who: "Player".to_string(), item: self.ecs_world.get::<String>(entity).unwrap().to_string() });
                 //puts the item in backpack
                 self.pickup_item(&entity)
             },
             None => { 
-                game_message(&format!("No item to pick up here"));
+                self.game_message(&format!("No item to pick up here"));
             },
         }
     }
 
     pub fn view_list(&self) -> Vec<u64> {
         let mut list = Vec::new();
-        for (id, (point, render)) in self.ecs_world.query::<(&Point, &Renderable)>()
+        for (id, (point, render, lvl)) in self.ecs_world.query::<(&Point, &Renderable, Option<&Level>)>()
         .with::<String>()
         .without::<InBackpack>().without::<Equipped>() //no ref/pointer here!
         .iter() {
-            if self.is_visible(point.x as usize, point.y as usize) {
+            if on_level(lvl, self.level) && self.is_visible(point.x as usize, point.y as usize) {
                 list.push(id.to_bits())
             }
         }
@@ -995,6 +926,7 @@ impl Universe {
     }
 
     pub fn use_item_ext(&mut self, id: u64) {
+        self.journal.record(JournalEntry::UseItem(id));
         //get player entity
         let mut play: Option<Entity> = None;
         for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
@@ -1010,14 +942,17 @@ impl Universe {
 
                 log!("Player uses item {}", id);
                 let item = hecs::Entity::from_bits(id); //restore
+                //check before using, consumables are gone afterwards
+                let cost = if self.ecs_world.get::<ProvidesHealing>(item).is_ok() { COST_MEDKIT } else { COST_DEFAULT };
                 self.use_item(&entity, &item);
-                self.end_turn();
+                self.end_turn(cost);
             },
             None => {},
         }
     }
 
     pub fn drop_item_ext(&mut self, id: u64) {
+        self.journal.record(JournalEntry::DropItem(id));
         //get player entity
         let mut play: Option<Entity> = None;
         for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
@@ -1041,6 +976,7 @@ impl Universe {
 
 
     pub fn change_money(&mut self, val: f32) {
+        self.journal.record(JournalEntry::ChangeMoney(val));
         //get player entity
         let mut play: Option<Entity> = None;
         for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
@@ -1056,27 +992,12 @@ impl Universe {
     }
 
     pub fn give_item(&mut self, name: String) {
-        let current_position = self.map.idx_xy(self.player_position);
-
-        let mut item: Option<Entity> = None;
-        //TODO: should be a dict lookup
-        if name == "Protein shake".to_string() {
-            item = Some(self.ecs_world.spawn((Point{x:current_position.0,y:current_position.1}, Renderable{glyph: RenderableGlyph::Medkit as u8, order: RenderOrder::Item}, "Protein shake".to_string(), Item{}, ProvidesFood{}, ProvidesQuench{}, Consumable{}, ToRemove{yes:false})));
-        }
-        if name == "Medkit".to_string() {
-            item = Some(self.ecs_world.spawn((Point{x:5, y:5}, Renderable{glyph:RenderableGlyph::Medkit as u8, order:RenderOrder::Item}, "Medkit".to_string(), Item{}, ToRemove{yes:false}, Consumable{}, ProvidesHealing{heal_amount:5})));
-        }
-        match item {
-            Some(it) => {
-                //puts the item in backpack
-                self.pickup_item(&it);
-            },
-            None => {},
-        }
-
+        self.journal.record(JournalEntry::GiveItem(name.clone()));
+        self.give_item_from(&name, &DATA.lock().unwrap());
     }
 
     pub fn set_player_stats(&mut self, new_stats: Vec<i32>) {
+        self.journal.record(JournalEntry::SetPlayerStats(new_stats.clone()));
         //get player entity
         let mut play: Option<Entity> = None;
         for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
@@ -1149,35 +1070,19 @@ impl Universe {
             Some(entity) => {
                 let turns_passed = Duration::hours(8).num_seconds();
 
+                self.autosave_before_wait();
                 //simulate all that time
-                for _ in 0..turns_passed {
-                    self.get_AI();
-                    //reenable when it makes sense to do so
-                    //no inter-AI combat yet
-                    //self.remove_dead();
-                    // needs rebalancing for 1s turns
-                    //self.survival_tick();
-                }
+                self.pass_time(turns_passed);
 
                 //rest!
-                let mut stats = self.ecs_world.get_mut::<CombatStats>(entity).unwrap();
-                //just set to max
-                stats.hp = stats.max_hp;
-                game_message("{gDone resting");
-
-
-                let mut gs = self.ecs_world.get_mut::<GameState>(entity).unwrap();
-                //add the current number of turns to game start
-                let cur_t = NaiveTime::from_hms(08, 00, 00).overflowing_add_signed(Duration::seconds(gs.turns));
-                let t = cur_t.0.overflowing_add_signed(Duration::hours(8));
-
-                // //t is a tuple (NaiveTime, i64)
-                let f = t.0.format("%H:%M:%S").to_string();
-                game_message(&format!("Time: {}", f));
+                {
+                    let mut stats = self.ecs_world.get_mut::<CombatStats>(entity).unwrap();
+                    //just set to max
+                    stats.hp = stats.max_hp;
+                }
+                self.emit(GameEvent::Rested{ who: "Player".to_string() });
 
-                //let mut gs = self.ecs_world.get_mut::<GameState>(entity).unwrap();
-                //update our turns counter
-                gs.turns += turns_passed;
+                self.calendar_time();
 
             },
             None => {},
@@ -1185,6 +1090,7 @@ impl Universe {
     }    
 
     pub fn wait(&mut self, opt: WaitType) {
+        self.journal.record(JournalEntry::Wait(opt));
         //get player entity
         let mut play: Option<Entity> = None;
         for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
@@ -1205,10 +1111,14 @@ impl Universe {
                         //game_message(&format!("End time: {}", f));
 
                         //add the current number of turns to game start
-                        let cur_t = NaiveTime::from_hms(08, 00, 00).overflowing_add_signed(Duration::seconds(gs.turns));
+                        let cur_t = gs.clock();
                         //returns a Duration
-                        let diff = end_t - cur_t.0;
+                        let diff = end_t - cur_t;
                         turns_passed = diff.num_seconds();
+                        //already past dusk, so the next one
+                        if turns_passed < 0 {
+                            turns_passed += Duration::days(1).num_seconds();
+                        }
                         //log!("{}", &format!("{} s", diff.num_seconds()));
                         //gs.turns += turns_passed;    
                     }
@@ -1227,40 +1137,153 @@ impl Universe {
                     turns_passed = Duration::hours(2).num_seconds();
                 }
                
-                //simulate all that time
-                for _ in 0..turns_passed {
-                    self.get_AI();
-                    //reenable when it makes sense to do so
-                    //no inter-AI combat yet
-                    //self.remove_dead();
-                    // needs rebalancing for 1s turns
-                    //self.survival_tick();
+                if turns_passed > 0 {
+                    self.autosave_before_wait();
                 }
+                //simulate all that time
+                self.pass_time(turns_passed);
 
-
-                //calculate time again
-                let mut gs = self.ecs_world.get_mut::<GameState>(entity).unwrap();
-                gs.turns += turns_passed; // add it here because otherwise the AI acts on final time
-                let cur_t = NaiveTime::from_hms(08, 00, 00).overflowing_add_signed(Duration::seconds(gs.turns));
-                // //t is a tuple (NaiveTime, i64)
-                let f = cur_t.0.format("%H:%M:%S").to_string();
-                game_message(&format!("Time: {}", f));
+                self.calendar_time();
             },
             None => {},
         }
     }
 
+    //JS calls it every tick, it's a no-op unless the console's reload has finished
+    pub fn apply_reload(&mut self) -> bool {
+        return self.apply_pending_reload();
+    }
+
+    //empty if the data file loaded fine
+    pub fn data_error(&self) -> String {
+        return self.data_error.clone().unwrap_or_default();
+    }
+
+    //JSON list of tile definitions, JS builds its palette from the glyphs and colors
+    pub fn tile_defs(&self) -> String {
+        return serde_json::to_string(&DATA.lock().unwrap().tiles).unwrap_or_default();
+    }
+
+    //same for entities, draw_entities() gives the ids
+    pub fn glyph_defs(&self) -> String {
+        return serde_json::to_string(&DATA.lock().unwrap().glyphs).unwrap_or_default();
+    }
+
+    //JSON list of log entries, starting with the given index (so that JS can fetch only the new ones)
+    pub fn log_entries(&self, from: usize) -> String {
+        let log = self.message_log.borrow();
+        let entries = log.entries();
+        let from = std::cmp::min(from, entries.len());
+        let new : Vec<&LogEntry> = entries.range(from..).collect();
+        return serde_json::to_string(&new).unwrap_or_default();
+    }
+
+    pub fn log_len(&self) -> usize {
+        return self.message_log.borrow().entries().len();
+    }
+
+    pub fn render_log(&self, format: LogFormat) -> String {
+        return self.message_log.borrow().render(format);
+    }
+
+    //JSON list of everything that happened since the last call
+    pub fn drain_events(&self) -> String {
+        return serde_json::to_string(&self.events.drain()).unwrap_or_default();
+    }
+
+    pub fn statistics(&self) -> String {
+        return serde_json::to_string(&*self.stats.borrow()).unwrap_or_default();
+    }
+
+    //JSON, for bug reports and regression tests
+    pub fn export_journal(&self) -> String {
+        return self.journal.to_json();
+    }
+
+    // replays on top of a fresh game, started with the seed the journal was recorded with
+    pub fn replay_journal(&mut self, data: String) -> bool {
+        match Journal::from_json(&data) {
+            Some(journal) => self.replay(&journal),
+            None => false,
+        }
+    }
+
     pub fn save_game(&self) -> String {
         log!("Saving game...");
         return saveload::save_game(self);
     }
 
-    pub fn load_save(&mut self, data: String) {
-        saveload::load_save(self, data);
-        // refresh FOV
-        let current_position = self.map.idx_xy(self.player_position);
-        self.fov_data.clear_fov(); // compute_fov does not clear the existing fov
-        self.fov.compute_fov(&mut self.fov_data, current_position.0 as usize, current_position.1 as usize, 6, true);
+    //same game as save_game, as bytes
+    pub fn save_game_compact(&self) -> Vec<u8> {
+        return saveload::save_game_compact(self);
+    }
+
+    pub fn set_save_format(&mut self, format: saveload::SaveFormat) {
+        self.save_format = format;
+    }
+
+    //JSON list of slots with their metadata
+    pub fn list_save_slots(&self) -> String {
+        return serde_json::to_string(&self.slot_list()).unwrap_or_default();
+    }
+
+    pub fn save_to_slot(&self, name: String) -> bool {
+        return self.save_slot(&name);
+    }
+
+    pub fn load_from_slot(&mut self, name: String) -> bool {
+        match self.load_slot_data(&name) {
+            //slots can hold either format
+            Some(data) => match compact_from_slot(&data) {
+                Some(bytes) => self.load_save_compact(bytes),
+                None => self.load_save(data),
+            },
+            None => {
+                self.game_message(&format!("{{rThere is no save called {}", name));
+                false
+            }
+        }
+    }
+
+    //for saves made before there were slots
+    pub fn import_save_slot(&self, name: String, data: String) -> bool {
+        return self.import_slot(&name, &data);
+    }
+
+    pub fn delete_save_slot(&self, name: String) -> bool {
+        return self.remove_slot(&name);
+    }
+
+    //JSON AutosavePolicy, missing fields keep their defaults
+    pub fn set_autosave_policy(&self, data: String) -> bool {
+        match serde_json::from_str::<AutosavePolicy>(&data) {
+            Ok(policy) => {
+                self.autosave.borrow_mut().policy = policy;
+                true
+            },
+            Err(e) => {
+                log!("{}", &format!("Bad autosave policy: {}", e));
+                false
+            }
+        }
+    }
+
+    pub fn autosave_policy(&self) -> String {
+        return serde_json::to_string(&self.autosave.borrow().policy).unwrap_or_default();
+    }
+
+    //false (and a message) if the save can't be loaded, the current game goes on then
+    pub fn load_save(&mut self, data: String) -> bool {
+        //the whole save goes in, otherwise a replay can't continue from it
+        self.journal.record(JournalEntry::LoadSave(data.clone()));
+        let res = saveload::load_save(self, data);
+        return self.finish_load(res);
+    }
+
+    pub fn load_save_compact(&mut self, data: Vec<u8>) -> bool {
+        self.journal.record(JournalEntry::LoadSaveCompact(data.clone()));
+        let res = saveload::load_save_compact(self, &data);
+        return self.finish_load(res);
     }
 
 }
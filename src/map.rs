@@ -1,24 +1,43 @@
 use serde::{Serialize, Deserialize};
 extern crate wasm_bindgen;
 
-use wasm_bindgen::prelude::*;
-
 use std::cmp::{max, min};
 use crate::utils::*;
 use crate::log;
 
-#[wasm_bindgen]
-#[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
-pub enum Cell {
-    Floor = 0,
-    Wall = 1,
-    Grass = 2,
-    Tree = 3,
-    FloorIndoor = 4,
-    Door = 5,
-    Mountain = 6,
-    Water = 7,
+//the tiles the code puts down itself (the map builders), the data file has to have them
+// everything else, e.g. the terrain in the map section, is up to the data file
+pub const BUILDER_TILES: [&str; 7] = ["pavement", "wall", "floor", "door", "stairs down", "stairs up", "tunnel"];
+
+//what a tile does and looks like, from the data file
+// maps (and saves) store the id, so ids have to stay the same once they're out there, like glyph ids
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TileDef {
+    pub id: u8,
+    pub name: String, //what the map builders know it by
+    #[serde(default)]
+    pub walkable: bool,
+    #[serde(default = "default_true")]
+    pub transparent: bool,
+    #[serde(default = "default_cost")]
+    pub cost: f32, //multiplies the time it takes to walk, and the A* step cost
+    #[serde(default)]
+    pub description: String, //shown when the player steps on it
+    pub glyph: String,
+    pub fg: (u8, u8, u8),
+    #[serde(default)]
+    pub bg: Option<(u8, u8, u8)>,
+}
+
+fn default_true() -> bool { true }
+fn default_cost() -> f32 { 1.0 }
+
+//stairs (or a manhole) leading to another level
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Exit {
+    pub idx: usize, //where it is on this level
+    pub level: usize,
+    pub dest: usize, //where it comes out on the other one
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -26,8 +45,15 @@ pub struct Map {
     pub tiles: Vec<u8>, //Vec<u8> can be passed by wasm_bindgen
     pub width: u32,
     pub height: u32,
+    #[serde(skip)] //rebuilt from entities on load
     blocked: Vec<bool>,
     pub revealed_tiles : Vec<bool>,
+    #[serde(skip)] //comes from the data file, indexed by id
+    tile_defs: Vec<Option<TileDef>>,
+    #[serde(default)] //older saves only have the town
+    pub name: String, //empty for the town
+    #[serde(default)]
+    pub exits: Vec<Exit>,
 }
 
 impl Map {
@@ -38,9 +64,50 @@ impl Map {
             blocked.push(false);
             revealed.push(false);
         }
-        let tiles = vec![Cell::Floor as u8; (w * h) as usize];
+        //whatever tile 0 is, the map builders fill it in
+        let tiles = vec![0; (w * h) as usize];
+
+        return Map{width: w, height: h, tiles: tiles, blocked: blocked, revealed_tiles: revealed, tile_defs: Vec::new(), name: String::new(), exits: Vec::new()};
+    }
+
+    pub fn set_tile_defs(&mut self, defs: &[TileDef]) {
+        self.tile_defs = Vec::new();
+        for def in defs.iter() {
+            let c = def.id as usize;
+            if c >= self.tile_defs.len() {
+                self.tile_defs.resize(c + 1, None);
+            }
+            self.tile_defs[c] = Some(def.clone());
+        }
+    }
+
+    //the id of the tile with that name, None if the data file has no such tile
+    pub fn tile(&self, name: &str) -> Option<u8> {
+        return self.tile_defs.iter().flatten().find(|d| d.name == name).map(|d| d.id);
+    }
 
-        return Map{width: w, height: h, tiles: tiles, blocked: blocked, revealed_tiles: revealed};
+    pub fn is_tile(&self, idx: usize, name: &str) -> bool {
+        return self.tile_def(idx).map_or(false, |d| d.name == name);
+    }
+
+    pub fn tile_def(&self, idx: usize) -> Option<&TileDef> {
+        match self.tile_defs.get(self.tiles[idx] as usize) {
+            Some(Some(def)) => Some(def),
+            _ => None,
+        }
+    }
+
+    //undefined tiles can be seen through but not walked on
+    pub fn is_tile_transparent(&self, idx: usize) -> bool {
+        return self.tile_def(idx).map_or(true, |d| d.transparent);
+    }
+
+    pub fn move_cost(&self, idx: usize) -> f32 {
+        return self.tile_def(idx).map_or(1.0, |d| d.cost);
+    }
+
+    pub fn exit_at(&self, idx: usize) -> Option<Exit> {
+        return self.exits.iter().find(|e| e.idx == idx).cloned();
     }
 
     // We're storing all the tiles in one big array, so we need a way to map an X,Y coordinate to
@@ -56,6 +123,10 @@ impl Map {
         (idx as i32 % self.width as i32, idx as i32 / self.width as i32)
     }
 
+    pub fn clear_blocked(&mut self) {
+        self.blocked = vec![false; (self.width * self.height) as usize];
+    }
+
     //blocked for pathfinding (eg. by other entities)
     pub fn set_tile_blocked(&mut self, idx : usize) {
         self.blocked[idx] = true;
@@ -74,8 +145,7 @@ impl Map {
         if !self.is_in_bounds(x,y) { return false };
         
         let idx = (y * self.width as i32) + x;
-        let walkables = vec![Cell::Floor as u8, Cell::FloorIndoor as u8, Cell::Grass as u8, Cell::Door as u8];
-        return walkables.contains(&self.tiles[idx as usize]);
+        return self.tile_def(idx as usize).map_or(false, |d| d.walkable);
     }
 
     pub fn is_in_bounds(&self, x:i32, y:i32) -> bool {
@@ -1,4 +1,4 @@
-use super::{InitialMapBuilder, MetaMapBuilder, BuilderMap, Map, Cell, Rect, Point};
+use super::{InitialMapBuilder, MetaMapBuilder, BuilderMap, BuildingTag, Map, Rect, Point, GameRng};
 use super::data_loader::*;
 use super::log; //macro
 //RNG
@@ -14,14 +14,14 @@ pub struct BSPTownBuilder {
 
 impl InitialMapBuilder for BSPTownBuilder {
     #[allow(dead_code)]
-    fn build_map(&mut self, build_data : &mut BuilderMap, data: &DataMaster) {
-        self.build(build_data);
+    fn build_map(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster) {
+        self.build(rng, build_data);
     }
 }
 
 impl MetaMapBuilder for BSPTownBuilder {
     #[allow(dead_code)]
-    fn build_map(&mut self, build_data : &mut BuilderMap, data: &DataMaster) {
+    fn build_map(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster) {
         //meta version panics if no submaps
         let submaps : Vec<Rect>;
         if let Some(submaps_builder) = &build_data.submaps {
@@ -30,18 +30,10 @@ impl MetaMapBuilder for BSPTownBuilder {
             panic!("Using BSP town as meta requires a builder with submap structures");
         }
 
-        self.build(build_data);
+        self.build(rng, build_data);
     }
 }
 
-#[derive(Debug)]
-enum BuildingTag {
-    Pub,
-    Hostel,
-    Hovel,
-    Unassigned,
-}
-
 impl BSPTownBuilder {
     #[allow(dead_code)]
     pub fn new() -> Box<BSPTownBuilder> {
@@ -52,7 +44,7 @@ impl BSPTownBuilder {
         })
     }
 
-    fn build(&mut self, build_data : &mut BuilderMap) {
+    fn build(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap) {
         let mut rooms : Vec<Rect> = Vec::new();
 
         //we work with submap bounds if we have them, else we work with the whole map
@@ -84,7 +76,7 @@ impl BSPTownBuilder {
         for y in sy..endy {
             for x in sx..endx {
                 let idx = build_data.map.xy_idx(x as i32, y as i32);
-                build_data.map.tiles[idx] = Cell::Floor as u8;
+                build_data.map.tiles[idx] = build_data.tiles.pavement;
             }
         }
 
@@ -93,15 +85,15 @@ impl BSPTownBuilder {
         //Rust is weird, ranges are inclusive at the beginning but exclusive at the end
         // for x in 0 ..build_data.map.width{
         //     let mut idx = build_data.map.xy_idx(x as i32, 0);
-        //     build_data.map.tiles[idx] = Cell::Wall as u8;
+        //     build_data.map.tiles[idx] = build_data.tiles.wall;
         //     idx = build_data.map.xy_idx(x as i32, build_data.map.height as i32-1);
-        //     build_data.map.tiles[idx] = Cell::Wall as u8;
+        //     build_data.map.tiles[idx] = build_data.tiles.wall;
         // }
         // for y in 0 ..build_data.map.height{
         //     let mut idx = build_data.map.xy_idx(0, y as i32);
-        //     build_data.map.tiles[idx] = Cell::Wall as u8;
+        //     build_data.map.tiles[idx] = build_data.tiles.wall;
         //     idx = build_data.map.xy_idx(build_data.map.width as i32-1, y as i32);
-        //     build_data.map.tiles[idx] = Cell::Wall as u8;
+        //     build_data.map.tiles[idx] = build_data.tiles.wall;
         // }
 
         //self.take_snapshot();
@@ -120,7 +112,7 @@ impl BSPTownBuilder {
         // room in there, we place it and add it to the rooms list.
         let mut n_rooms = 0;
         while n_rooms < 240 {
-            let data = self.get_random_rect();
+            let data = self.get_random_rect(rng);
             let rect = data.0;
             //log!("{}", format!("random rect: {:?}", data));
 
@@ -128,7 +120,7 @@ impl BSPTownBuilder {
             let rect_width = i32::abs(rect.x1 - rect.x2);
             let rect_height = i32::abs(rect.y1 - rect.y2);
             if rect_width > MIN_ROOM_SIZE && rect_height > MIN_ROOM_SIZE { 
-                let candidate = self.get_random_sub_rect(rect);
+                let candidate = self.get_random_sub_rect(rng, rect);
                 //log!("{}", format!("rect candidate: {:?}", candidate));
 
                 if self.is_possible(candidate, &build_data, &rooms) {
@@ -157,7 +149,7 @@ impl BSPTownBuilder {
                 for x in room.x1 .. room.x2 {
                     let idx = build_data.map.xy_idx(x, y);
                     if idx > 0 && idx < ((build_data.map.width * build_data.map.height)-1) as usize {
-                        build_data.map.tiles[idx] = Cell::Wall as u8;
+                        build_data.map.tiles[idx] = build_data.tiles.wall;
                     }
                 }
             }
@@ -167,7 +159,7 @@ impl BSPTownBuilder {
                 for x in room.x1+1 .. room.x2-1 {
                     let idx = build_data.map.xy_idx(x, y);
                     if idx > 0 && idx < ((build_data.map.width * build_data.map.height)-1) as usize {
-                        build_data.map.tiles[idx] = Cell::FloorIndoor as u8;
+                        build_data.map.tiles[idx] = build_data.tiles.floor;
                     }
                 }
             }
@@ -175,24 +167,23 @@ impl BSPTownBuilder {
 
             //build doors
             let cent = room.center();
-            let mut rng = rand::thread_rng();
             let door_direction = rng.gen_range(1, 4);
             match door_direction {
                 1 => { 
                     let idx = build_data.map.xy_idx(cent.0, room.y1); //north
-                    build_data.map.tiles[idx] = Cell::Door as u8;
+                    build_data.map.tiles[idx] = build_data.tiles.door;
                 }
                 2 => { 
                     let idx = build_data.map.xy_idx(cent.0, room.y2-1); //south
-                    build_data.map.tiles[idx] = Cell::Door as u8;
+                    build_data.map.tiles[idx] = build_data.tiles.door;
                 }
                 3 => { 
                     let idx = build_data.map.xy_idx(room.x1, cent.1); //west
-                    build_data.map.tiles[idx] = Cell::Door as u8;
+                    build_data.map.tiles[idx] = build_data.tiles.door;
                 }
                 _ => { 
                     let idx = build_data.map.xy_idx(room.x2-1, cent.1); //east
-                    build_data.map.tiles[idx] = Cell::Door as u8;
+                    build_data.map.tiles[idx] = build_data.tiles.door;
                 }
             }
             //build_data.take_snapshot();
@@ -200,7 +191,7 @@ impl BSPTownBuilder {
 
         let building_size = self.sort_buildings(&rooms_copy);
         log!("{}", &format!("Buildings sorted: {:?}", building_size));
-        self.building_factory(build_data, &rooms_copy, &building_size);
+        self.building_factory(rng, build_data, &rooms_copy, &building_size);
     }
 
     fn sort_buildings(&mut self, buildings: &Vec<Rect>) -> Vec<(usize, i32, BuildingTag)> 
@@ -229,6 +220,7 @@ impl BSPTownBuilder {
     }
 
     fn building_factory(&mut self, 
+        rng: &mut GameRng,
         build_data : &mut BuilderMap, 
         buildings: &Vec<Rect>, 
         building_index : &[(usize, i32, BuildingTag)]) 
@@ -238,46 +230,72 @@ impl BSPTownBuilder {
             // find building type entry with matching i
             let b_index = building_index.iter().position(|x| x.0 == i).unwrap(); //position() returns an Option
             let build_type = &building_index[b_index].2;
+            build_data.buildings.push((*build_type, *building));
             match build_type {
                 BuildingTag::Pub => self.build_pub(&building, build_data),
                 BuildingTag::Hostel => self.build_capsule_hotel(&building, build_data),
+                BuildingTag::Hovel => self.build_hovel(&building, build_data),
                 _ => {}
             }
+            //the scenario says which kind of building the player starts in
+            if *build_type == build_data.start_building && build_data.starting_position.is_none() {
+                self.place_player(&building, build_data);
+            }
+        }
+        //no such building in this town
+        if build_data.starting_position.is_none() {
+            log!("{}", &format!("No {:?} to start in", build_data.start_building));
+            self.place_player(&buildings[building_index[0].0], build_data);
         }
     }
 
+    //the center if it's free, otherwise the closest floor
+    fn place_player(&self, building: &Rect, build_data : &mut BuilderMap) {
+        let cent = building.center();
+        let mut tiles = self.indoor_tiles(building, build_data);
+        tiles.sort_by_key(|&idx| {
+            let (x, y) = build_data.map.idx_xy(idx);
+            (x - cent.0).abs() + (y - cent.1).abs()
+        });
+        let (x, y) = match tiles.first() {
+            Some(&idx) => build_data.map.idx_xy(idx),
+            None => cent,
+        };
+        build_data.starting_position = Some(Point{ x : x, y : y });
+    }
+
     fn build_pub(&mut self, building: &Rect, build_data : &mut BuilderMap) 
     {
         //log!("{}", &format!("pub: {:?}", building));
-        // Place the player
-        let cent = building.center();
-        build_data.starting_position = Some(Point{
-            x : cent.0,
-            y : cent.1
-        });
-        let player_idx = build_data.map.xy_idx(cent.0, cent.1);
-    
-        // Place NPC  
-        let mut to_place : Vec<&str> = vec!["Barkeep", "Patron", "Patron",
-        // place props
-        "Table", "Chair", "Table", "Chair"];
-        let mut rng = rand::thread_rng();
+        // NPCs and props come from the spawn tables (which leave the player's tile alone)
+        let area : Vec<usize> = self.indoor_tiles(building, build_data);
+        build_data.spawn_areas.push((BuildingTag::Pub, 0, area));
+    }
 
+    fn build_hovel(&mut self, building: &Rect, build_data : &mut BuilderMap)
+    {
+        let area = self.indoor_tiles(building, build_data);
+        build_data.spawn_areas.push((BuildingTag::Hovel, 0, area));
+    }
+
+    fn indoor_tiles(&self, building: &Rect, build_data : &BuilderMap) -> Vec<usize> {
+        let mut tiles = Vec::new();
         for y in building.y1 .. building.y2 {
             for x in building.x1 .. building.x2 {
                 let idx = build_data.map.xy_idx(x, y);
-                if build_data.map.tiles[idx] == Cell::FloorIndoor as u8 && idx != player_idx && rng.gen_range(1, 3)==1 && !to_place.is_empty() {
-                    let entity_tag = to_place[0];
-                    to_place.remove(0);
-                    build_data.list_spawns.push((idx, entity_tag.to_string()));
+                if build_data.map.tiles[idx] == build_data.tiles.floor {
+                    tiles.push(idx);
                 }
             }
         }
+        tiles
     }
 
     fn build_capsule_hotel(&mut self, building: &Rect, build_data : &mut BuilderMap) 
     {
         //log!("{}", &format!("hotel: {:?}", building));
+        //one spot per capsule, the spawn tables decide what goes there
+        let mut capsules : Vec<usize> = Vec::new();
         let start_x = building.x1;
         let end_x = building.x2-1;
         let start_y = building.y1;
@@ -289,7 +307,7 @@ impl BSPTownBuilder {
             if (x-start_x) > 1 && (x-start_x) % 3 == 0 {
                 for y in start_y..end_y {
                     let idx = build_data.map.xy_idx(x, y);
-                    build_data.map.tiles[idx] = Cell::Wall as u8;
+                    build_data.map.tiles[idx] = build_data.tiles.wall;
                 }
             }
                     
@@ -300,17 +318,17 @@ impl BSPTownBuilder {
                     // same trick as above
                     if (y-start_y) > 1 && (y-start_y) % 3 == 0 {
                         let idx = build_data.map.xy_idx(x, y);
-                        build_data.map.tiles[idx] = Cell::Wall as u8;
+                        build_data.map.tiles[idx] = build_data.tiles.wall;
                     }
                 }
             }
 
-            // place one bed per capsule
+            // one spot per capsule
             if (((x-start_x) > 0 && (x-start_x) < 3+1) || (x-start_x) > 6) && (x-start_x) % 3 == 1 {
                 for y in start_y..end_y {
                     if (y-start_y) % 3 == 1 {
                         let idx = build_data.map.xy_idx(x, y);
-                        build_data.list_spawns.push((idx, "Bed".to_string()));
+                        capsules.push(idx);
                     }
                 }
             }
@@ -322,7 +340,7 @@ impl BSPTownBuilder {
                 for y in start_y..end_y {
                     if (y-start_y) >= 1 && (y-start_y) % 3 == 1 {
                         let idx = build_data.map.xy_idx(x, y);
-                        build_data.map.tiles[idx] = Cell::Door as u8;
+                        build_data.map.tiles[idx] = build_data.tiles.door;
                     }
                 }
             }
@@ -337,7 +355,9 @@ impl BSPTownBuilder {
         }
 
         let idx = build_data.map.xy_idx(x, building.y2-1);
-        build_data.map.tiles[idx] = Cell::Door as u8;
+        build_data.map.tiles[idx] = build_data.tiles.door;
+
+        build_data.spawn_areas.push((BuildingTag::Hostel, 0, capsules));
     }
 
 
@@ -356,15 +376,14 @@ impl BSPTownBuilder {
     }
 
     //helpers
-    fn get_random_rect(&mut self) -> (Rect, usize) {
+    fn get_random_rect(&mut self, rng: &mut GameRng) -> (Rect, usize) {
         if self.rects.len() == 1 { return (self.rects[0], 0); }
-        let mut rng = rand::thread_rng();
         let idx = (rng.gen_range(1, self.rects.len() as i32)-1) as usize; 
         //let idx = (rng.roll_dice(1, self.rects.len() as i32)-1) as usize;
         (self.rects[idx], idx)
     }
 
-    fn get_random_sub_rect(&self, rect : Rect) -> Rect {
+    fn get_random_sub_rect(&self, rng: &mut GameRng, rect : Rect) -> Rect {
         let mut result = rect;
         
         //let rect_width = i32::abs(rect.x1 - rect.x2);
@@ -372,7 +391,6 @@ impl BSPTownBuilder {
 
         //let w = i32::max(3, rng.roll_dice(1, i32::min(rect_width, 10))-1) + 1;
         //let h = i32::max(3, rng.roll_dice(1, i32::min(rect_height, 10))-1) + 1;
-        let mut rng = rand::thread_rng();
         
         //let w = rng.gen_range(4,6);
         //let h = rng.gen_range(4,6);
@@ -427,7 +445,7 @@ impl BSPTownBuilder {
                 if y < 1 { can_build = false; }
                 if can_build {
                     let idx = build_data.map.xy_idx(x, y);
-                    if build_data.map.tiles[idx] != Cell::Floor as u8 { //key change
+                    if build_data.map.tiles[idx] != build_data.tiles.pavement { //key change
                         //console::log(&format!("Candidate {:?} failed the tile check!", rect));
                         can_build = false; 
                     }
@@ -0,0 +1,164 @@
+use super::{MetaMapBuilder, BuilderMap, BuildingTag, Map, Exit, Rect, GameRng};
+use super::data_loader::*;
+
+const SEWER_SPACING_X : i32 = 16;
+const SEWER_SPACING_Y : i32 = 12;
+
+// the levels below and above the town: a cellar under the pub, the hostel's upper floor and the sewers
+// every level is as big as the town, so stairs come out right above/below each other
+pub struct LevelsBuilder {}
+
+impl MetaMapBuilder for LevelsBuilder {
+    fn build_map(&mut self, _rng: &mut GameRng, build_data : &mut BuilderMap, _data: &DataMaster) {
+        self.build(build_data);
+    }
+}
+
+impl LevelsBuilder {
+    pub fn new() -> Box<LevelsBuilder> {
+        Box::new(LevelsBuilder{})
+    }
+
+    fn build(&mut self, build_data : &mut BuilderMap) {
+        let buildings = build_data.buildings.clone();
+        if let Some((_, building)) = buildings.iter().find(|b| b.0 == BuildingTag::Pub) {
+            self.build_cellar(building, build_data);
+        }
+        if let Some((_, building)) = buildings.iter().find(|b| b.0 == BuildingTag::Hostel) {
+            self.build_upstairs(building, build_data);
+        }
+        self.build_sewers(&buildings, build_data);
+    }
+
+    //all wall, the builders carve it out
+    fn new_level(&self, name: &str, build_data : &mut BuilderMap) -> usize {
+        //a copy, so that it knows what tiles are
+        let mut map : Map = build_data.map.clone();
+        map.tiles = vec![build_data.tiles.wall; map.tiles.len()];
+        map.revealed_tiles = vec![false; map.tiles.len()];
+        map.clear_blocked();
+        map.exits.clear();
+        map.name = name.to_string();
+        build_data.levels.push(map);
+        return build_data.levels.len();
+    }
+
+    //both ways, at the same spot on both levels
+    fn add_stairs(&self, idx: usize, from: usize, to: usize, down: bool, build_data : &mut BuilderMap) {
+        let (down_id, up_id) = (build_data.tiles.stairs_down, build_data.tiles.stairs_up);
+        let (here, there) = if down { (down_id, up_id) } else { (up_id, down_id) };
+        let map = build_data.level_map_mut(from);
+        map.tiles[idx] = here;
+        map.exits.push(Exit{ idx: idx, level: to, dest: idx });
+        let map = build_data.level_map_mut(to);
+        map.tiles[idx] = there;
+        map.exits.push(Exit{ idx: idx, level: from, dest: idx });
+    }
+
+    //the floor furthest from the middle of the building, out of everyone's way (and off the beds, if it can)
+    fn stairs_spot(&self, building: &Rect, build_data : &BuilderMap) -> Option<usize> {
+        let cent = building.center();
+        let reserved : Vec<usize> = build_data.spawn_areas.iter().filter(|a| a.1 == 0).flat_map(|a| a.2.iter().cloned()).collect();
+        let start = build_data.starting_position.as_ref().map(|p| build_data.map.xy_idx(p.x, p.y));
+        let mut tiles = Vec::new();
+        for y in building.y1 .. building.y2 {
+            for x in building.x1 .. building.x2 {
+                let idx = build_data.map.xy_idx(x, y);
+                if build_data.map.is_tile(idx, "floor") && Some(idx) != start {
+                    tiles.push(idx);
+                }
+            }
+        }
+        tiles.sort_by_key(|&idx| {
+            let (x, y) = build_data.map.idx_xy(idx);
+            (reserved.contains(&idx), -((x - cent.0).abs() + (y - cent.1).abs()))
+        });
+        return tiles.first().cloned();
+    }
+
+    //one big room under the whole pub
+    fn build_cellar(&self, building: &Rect, build_data : &mut BuilderMap) {
+        let stairs = match self.stairs_spot(building, build_data) {
+            Some(idx) => idx,
+            None => return,
+        };
+        let level = self.new_level("pub cellar", build_data);
+        let floor = build_data.tiles.floor;
+        let mut area = Vec::new();
+        for y in building.y1 .. building.y2 {
+            for x in building.x1 .. building.x2 {
+                let idx = build_data.map.xy_idx(x, y);
+                build_data.level_map_mut(level).tiles[idx] = floor;
+                area.push(idx);
+            }
+        }
+        self.add_stairs(stairs, 0, level, true, build_data);
+        build_data.spawn_areas.push((BuildingTag::Cellar, level, area));
+    }
+
+    //same capsules as downstairs
+    fn build_upstairs(&self, building: &Rect, build_data : &mut BuilderMap) {
+        let stairs = match self.stairs_spot(building, build_data) {
+            Some(idx) => idx,
+            None => return,
+        };
+        let level = self.new_level("hostel upper floor", build_data);
+        let (door, wall) = (build_data.tiles.door, build_data.tiles.wall);
+        for y in building.y1 .. building.y2 {
+            for x in building.x1 .. building.x2 {
+                let idx = build_data.map.xy_idx(x, y);
+                let mut tile = build_data.map.tiles[idx];
+                //no doors to the street up here
+                let edge = x == building.x1 || x == building.x2-1 || y == building.y1 || y == building.y2-1;
+                if edge && tile == door {
+                    tile = wall;
+                }
+                build_data.level_map_mut(level).tiles[idx] = tile;
+            }
+        }
+        self.add_stairs(stairs, 0, level, false, build_data);
+
+        let capsules : Vec<usize> = build_data.spawn_areas.iter()
+            .filter(|a| a.0 == BuildingTag::Hostel && a.1 == 0)
+            .flat_map(|a| a.2.iter().cloned()).collect();
+        build_data.spawn_areas.push((BuildingTag::Upstairs, level, capsules));
+    }
+
+    //a grid of tunnels, lined up with a manhole in the streets
+    fn build_sewers(&self, buildings: &[(BuildingTag, Rect)], build_data : &mut BuilderMap) {
+        let cent = (build_data.map.width as i32 / 2, build_data.map.height as i32 / 2);
+        let start = build_data.starting_position.as_ref().map(|p| build_data.map.xy_idx(p.x, p.y));
+        let mut streets : Vec<usize> = (0..build_data.map.tiles.len()).filter(|&idx| {
+            let (x, y) = build_data.map.idx_xy(idx);
+            let in_building = buildings.iter().any(|b| x >= b.1.x1 && x < b.1.x2 && y >= b.1.y1 && y < b.1.y2);
+            build_data.map.is_tile(idx, "pavement") && ZoneType::of(&build_data.map, idx) == ZoneType::Streets
+                && !in_building && Some(idx) != start
+                && x > 1 && y > 1 && x < build_data.map.width as i32 - 2 && y < build_data.map.height as i32 - 2
+        }).collect();
+        streets.sort_by_key(|&idx| {
+            let (x, y) = build_data.map.idx_xy(idx);
+            (x - cent.0).abs() + (y - cent.1).abs()
+        });
+        let manhole = match streets.first() {
+            Some(&idx) => idx,
+            None => return,
+        };
+
+        let level = self.new_level("sewers", build_data);
+        let tunnel = build_data.tiles.tunnel;
+        let (mx, my) = build_data.map.idx_xy(manhole);
+        let (w, h) = (build_data.map.width as i32, build_data.map.height as i32);
+        let mut area = Vec::new();
+        for y in 2..h-2 {
+            for x in 2..w-2 {
+                if (x - mx) % SEWER_SPACING_X == 0 || (y - my) % SEWER_SPACING_Y == 0 {
+                    let idx = build_data.map.xy_idx(x, y);
+                    build_data.level_map_mut(level).tiles[idx] = tunnel;
+                    area.push(idx);
+                }
+            }
+        }
+        self.add_stairs(manhole, 0, level, true, build_data);
+        build_data.spawn_areas.push((BuildingTag::Sewer, level, area));
+    }
+}
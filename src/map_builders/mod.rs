@@ -1,9 +1,11 @@
-use super::{Map, Cell, Rect, Point};
+use super::{Map, Exit, Rect, Point, GameRng};
 use super::fastnoise;
 use super::data_loader;
-use super::data_loader::DataMaster;
+use super::data_loader::{DataMaster, DataError};
 use super::log;
 
+use serde::{Serialize, Deserialize};
+
 mod noise_map;
 use noise_map::NoiseMapBuilder;
 
@@ -13,12 +15,80 @@ use bsp_town::BSPTownBuilder;
 mod rectangle_builder;
 use rectangle_builder::RectBuilder;
 
+mod levels_builder;
+use levels_builder::LevelsBuilder;
+
+mod table_spawner;
+use table_spawner::TableSpawner;
+
+//what a building is for - decides its layout and which spawn tables apply
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BuildingTag {
+    Pub,
+    Hostel,
+    Hovel,
+    Cellar, //below the pub
+    Upstairs, //the hostel's upper floor
+    Sewer,
+    Unassigned,
+}
+
+
+//ids of the tiles the builders put down, looked up once per build
+#[derive(Clone, Copy, Default)]
+pub struct BuilderTiles {
+    pub pavement: u8,
+    pub wall: u8,
+    pub floor: u8,
+    pub door: u8,
+    pub stairs_down: u8,
+    pub stairs_up: u8,
+    pub tunnel: u8,
+    pub ter_wall: u8, //the terrain the data file's map section asks for
+    pub ter_ground: u8,
+    pub ter_bound: u8,
+}
+
+impl BuilderTiles {
+    pub fn new(map: &Map, data: &DataMaster) -> Result<BuilderTiles, DataError> {
+        let tile = |name: &str| map.tile(name).ok_or_else(|| DataError::MissingTile(name.to_string()));
+        Ok(BuilderTiles{
+            pavement: tile("pavement")?,
+            wall: tile("wall")?,
+            floor: tile("floor")?,
+            door: tile("door")?,
+            stairs_down: tile("stairs down")?,
+            stairs_up: tile("stairs up")?,
+            tunnel: tile("tunnel")?,
+            ter_wall: tile(&data.map.ter_wall)?,
+            ter_ground: tile(&data.map.ter_ground)?,
+            ter_bound: tile(&data.map.ter_bound)?,
+        })
+    }
+}
 
 pub struct BuilderMap {
     pub map : Map,
+    pub tiles : BuilderTiles,
     pub submaps: Option<Vec<Rect>>,
     pub starting_position : Option<Point>,
-    pub list_spawns : Vec<(usize, String)>,
+    pub list_spawns : Vec<(usize, usize, String)>, //level, tile, name
+    pub spawn_areas : Vec<(BuildingTag, usize, Vec<usize>)>, //tiles (on a level) each building's spawn tables can use
+    pub buildings : Vec<(BuildingTag, Rect)>,
+    pub levels : Vec<Map>, //the ones below and above the town, level 1 onwards
+    pub hour : u32, //time of day the map is built at, for spawn tables
+    pub start_building : BuildingTag, //where the player starts
+}
+
+impl BuilderMap {
+    //level 0 is the town
+    pub fn level_map(&self, level: usize) -> &Map {
+        if level == 0 { &self.map } else { &self.levels[level-1] }
+    }
+
+    pub fn level_map_mut(&mut self, level: usize) -> &mut Map {
+        if level == 0 { &mut self.map } else { &mut self.levels[level-1] }
+    }
 }
 
 pub struct BuilderChain {
@@ -34,9 +104,15 @@ impl BuilderChain {
             builders: Vec::new(),
             build_data : BuilderMap {
                 map: Map::new(width as u32, height as u32),
+                tiles: BuilderTiles::default(),
                 submaps: None,
                 starting_position: None,
                 list_spawns: Vec::new(),
+                spawn_areas: Vec::new(),
+                buildings: Vec::new(),
+                levels: Vec::new(),
+                hour: 8,
+                start_building: BuildingTag::Pub,
             }
         }
     }
@@ -53,19 +129,24 @@ impl BuilderChain {
         self.builders.push(metabuilder);
     }
 
-    pub fn build_map(&mut self, data: &DataMaster) {
+    //fails if the data file lacks a tile the builders need
+    pub fn build_map(&mut self, rng: &mut GameRng, data: &DataMaster) -> Result<(), DataError> {
+        //builders ask the map what is walkable
+        self.build_data.map.set_tile_defs(&data.tiles);
+        self.build_data.tiles = BuilderTiles::new(&self.build_data.map, data)?;
         match &mut self.starter {
             None => panic!("Cannot run a map builder chain without a starting build system"),
             Some(starter) => {
                 // Build the starting map
-                starter.build_map(&mut self.build_data, &data);
+                starter.build_map(rng, &mut self.build_data, &data);
             }
         }
 
         // Build additional layers in turn
         for metabuilder in self.builders.iter_mut() {
-            metabuilder.build_map(&mut self.build_data, &data);
+            metabuilder.build_map(rng, &mut self.build_data, &data);
         }
+        return Ok(());
     }
 
     // pub fn spawn_entities(&mut self) {
@@ -82,11 +163,11 @@ impl BuilderChain {
 // }
 
 pub trait InitialMapBuilder {
-    fn build_map(&mut self, build_data : &mut BuilderMap, data: &DataMaster);
+    fn build_map(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster);
 }
 
 pub trait MetaMapBuilder {    
-    fn build_map(&mut self, build_data : &mut BuilderMap, data: &DataMaster);
+    fn build_map(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster);
 }
 
 //Factory function for builder
@@ -96,5 +177,7 @@ pub fn random_builder(width: i32, height: i32) -> BuilderChain {
     builder.start_with(NoiseMapBuilder::new());
     builder.with(RectBuilder::new());
     builder.with(BSPTownBuilder::new());
+    builder.with(LevelsBuilder::new());
+    builder.with(TableSpawner::new());
     builder
 }
\ No newline at end of file
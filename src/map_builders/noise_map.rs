@@ -1,5 +1,7 @@
 
-use super::{InitialMapBuilder, BuilderMap, Map, Cell};
+use super::{InitialMapBuilder, BuilderMap, Map, GameRng};
+//RNG
+use rand::Rng;
 use super::fastnoise::*;
 use super::data_loader::*;
 use super::log; //macro
@@ -7,9 +9,9 @@ use super::log; //macro
 pub struct NoiseMapBuilder {}
 
 impl InitialMapBuilder for NoiseMapBuilder {
-    fn build_map(&mut self, build_data : &mut BuilderMap, data: &DataMaster)  {
+    fn build_map(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster)  {
         //let mut map = Map::new(20,20);
-        self.noise_build(build_data, &data);
+        self.noise_build(rng, build_data, &data);
     }
 }
 
@@ -19,7 +21,7 @@ impl NoiseMapBuilder {
         Box::new(NoiseMapBuilder{})
     }
 
-    fn noise_build(&mut self, build_data : &mut BuilderMap, data: &DataMaster) {
+    fn noise_build(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster) {
         //config
         //log!("Building map...");
         // leads to 'cannot recursively acquire mutex'
@@ -28,9 +30,7 @@ impl NoiseMapBuilder {
 
         //noise
         //generate noise
-        let mut rng = rand::thread_rng();
-        let mut noise = FastNoise::seeded(10001 as u64);
-        //let mut noise = FastNoise::seeded(rng.gen_range(1, 65537) as u64);
+        let mut noise = FastNoise::seeded(rng.gen_range(1, 65537) as u64);
         noise.set_noise_type(NoiseType::SimplexFractal);
         noise.set_fractal_type(FractalType::FBM);
         
@@ -47,6 +47,7 @@ impl NoiseMapBuilder {
         //noise.set_frequency(0.085);
         //noise.set_frequency(0.45);
 
+        let (wall, ground, bound) = (build_data.tiles.ter_wall, build_data.tiles.ter_ground, build_data.tiles.ter_bound);
         for x in 0..build_data.map.width-1 {
             for y in 0..build_data.map.height-1 {
                 let mut n = noise.get_noise(x as f32, y as f32);
@@ -57,9 +58,9 @@ impl NoiseMapBuilder {
                 //for small map
                 //if n > 125.0 || n < -125.0 {
                 if n > 140.0 {    
-                    build_data.map.tiles[idx] = data.map.ter_wall as u8;
+                    build_data.map.tiles[idx] = wall;
                 } else {
-                    build_data.map.tiles[idx] = data.map.ter_ground as u8;
+                    build_data.map.tiles[idx] = ground;
                 }
                 //log!("{}", &format!("Tile: x{} y{} {}", x,y, state.tiles[xy_idx(x,y)]));
             }
@@ -70,15 +71,15 @@ impl NoiseMapBuilder {
         // Make the boundaries walls
         for x in 0..build_data.map.width-1 {
             let mut idx = build_data.map.xy_idx(x as i32, 0);
-            build_data.map.tiles[idx] = data.map.ter_bound as u8;
+            build_data.map.tiles[idx] = bound;
             idx = build_data.map.xy_idx(x as i32, build_data.map.height as i32-1);
-            build_data.map.tiles[idx] = data.map.ter_bound as u8;
+            build_data.map.tiles[idx] = bound;
         }
         for y in 0..build_data.map.height-1 {
             let mut idx = build_data.map.xy_idx(0, y as i32); 
-            build_data.map.tiles[idx] = data.map.ter_bound as u8;
+            build_data.map.tiles[idx] = bound;
             idx = build_data.map.xy_idx(build_data.map.width as i32-1, y as i32);
-            build_data.map.tiles[idx] = data.map.ter_bound as u8;
+            build_data.map.tiles[idx] = bound;
         }
 
         //map
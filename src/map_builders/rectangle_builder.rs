@@ -1,11 +1,11 @@
-use super::{MetaMapBuilder, BuilderMap, Cell, Rect};
+use super::{MetaMapBuilder, BuilderMap, Rect, GameRng};
 use super::data_loader::*;
 use super::log; //macro
 
 pub struct RectBuilder {}
 
 impl MetaMapBuilder for RectBuilder {
-    fn build_map(&mut self, build_data : &mut BuilderMap, data: &DataMaster)  {
+    fn build_map(&mut self, _rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster)  {
         self.build(build_data, data);
     }
 }
@@ -36,7 +36,7 @@ impl RectBuilder {
         for y in rect.1 .. max_y {
             for x in rect.0 .. max_x {
                 let idx = build_data.map.xy_idx(x, y);
-                build_data.map.tiles[idx] = Cell::Floor as u8;
+                build_data.map.tiles[idx] = build_data.tiles.pavement;
             }
         }
         //build_data.take_snapshot();
@@ -58,7 +58,7 @@ impl RectBuilder {
                 //log!("{}", &format!("X,Y {:?},{:?} - num floors north: {:?} ", x, y, add));
                 let idx = build_data.map.xy_idx(x as i32, y as i32);
                 //Rust's ternary expression
-                num_floors[x][y] = if build_data.map.tiles[idx] == data.map.ter_ground as u8 { 1 + add } else {0};
+                num_floors[x][y] = if build_data.map.is_tile(idx, &data.map.ter_ground) { 1 + add } else {0};
             }
         }
 
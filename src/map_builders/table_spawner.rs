@@ -0,0 +1,87 @@
+use super::{MetaMapBuilder, BuilderMap, GameRng};
+use super::data_loader::*;
+//RNG
+use rand::Rng;
+
+// rolls the spawn tables from the data file - buildings first, then the zones outside of them
+pub struct TableSpawner {}
+
+impl MetaMapBuilder for TableSpawner {
+    fn build_map(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster) {
+        self.build(rng, build_data, data);
+    }
+}
+
+impl TableSpawner {
+    pub fn new() -> Box<TableSpawner> {
+        Box::new(TableSpawner{})
+    }
+
+    fn build(&mut self, rng: &mut GameRng, build_data : &mut BuilderMap, data: &DataMaster) {
+        //one thing per tile, and nothing on the stairs
+        let mut used : Vec<Vec<bool>> = Vec::new();
+        for level in 0..build_data.levels.len()+1 {
+            let map = build_data.level_map(level);
+            let mut tiles = vec![false; map.tiles.len()];
+            for exit in map.exits.iter() {
+                tiles[exit.idx] = true;
+            }
+            used.push(tiles);
+        }
+        if let Some(start) = &build_data.starting_position {
+            used[0][build_data.map.xy_idx(start.x, start.y)] = true;
+        }
+
+        let areas = build_data.spawn_areas.clone();
+        for (tag, level, tiles) in areas.iter() {
+            for table in data.spawn_tables.iter().filter(|t| t.key == SpawnKey::Building(*tag)) {
+                self.roll(rng, table, *level, tiles, &mut used[*level], build_data);
+            }
+        }
+
+        //zones are in the town, and don't include what the buildings already have
+        let mut in_building = vec![false; build_data.map.tiles.len()];
+        for (_, _, tiles) in areas.iter().filter(|a| a.1 == 0) {
+            for idx in tiles.iter() {
+                in_building[*idx] = true;
+            }
+        }
+        for table in data.spawn_tables.iter() {
+            if let SpawnKey::Zone(zone) = table.key {
+                let tiles : Vec<usize> = (0..build_data.map.tiles.len()).filter(|&idx| {
+                    let (x, y) = build_data.map.idx_xy(idx);
+                    !in_building[idx] && build_data.map.is_tile_walkable(x, y) && ZoneType::of(&build_data.map, idx) == zone
+                }).collect();
+                self.roll(rng, table, 0, &tiles, &mut used[0], build_data);
+            }
+        }
+    }
+
+    fn roll(&self, rng: &mut GameRng, table: &SpawnTable, level: usize, tiles: &[usize], used: &mut [bool], build_data : &mut BuilderMap) {
+        let entries : Vec<&SpawnEntry> = table.entries.iter().filter(|e| e.active_at(build_data.hour)).collect();
+        let total : u32 = entries.iter().map(|e| e.weight).sum();
+        if total == 0 {
+            return;
+        }
+
+        let count = if table.fill { tiles.len() as u32 } else { rng.gen_range(table.min, table.max + 1) };
+        for _ in 0..count {
+            //stops early if the area is full
+            let free : Vec<usize> = tiles.iter().filter(|&&idx| !used[idx]).cloned().collect();
+            if free.is_empty() {
+                break;
+            }
+            let idx = free[rng.gen_range(0, free.len())];
+
+            let mut pick = rng.gen_range(0, total);
+            for e in entries.iter() {
+                if pick < e.weight {
+                    used[idx] = true;
+                    build_data.list_spawns.push((level, idx, e.name.clone()));
+                    break;
+                }
+                pick -= e.weight;
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//the message log, stored on the Rust side
+//messages use a tiny markup: {r red}, {g green}, {gr gray}, {c cyan}, {y yellow}
+//an unclosed tag runs until the end of the message, e.g. "{rYou are DEAD!"
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Color { Red, Green, Gray, Cyan, Yellow }
+
+impl Color {
+    //longest first, so that {gr doesn't get read as {g + "r"
+    const TAGS: [(&'static str, Color); 5] = [("gr", Color::Gray), ("r", Color::Red), ("g", Color::Green), ("c", Color::Cyan), ("y", Color::Yellow)];
+
+    pub fn css(&self) -> &'static str {
+        match self {
+            Color::Red => "rgb(255,0,0)",
+            Color::Green => "rgb(0,255,0)",
+            Color::Gray => "rgb(127,127,127)",
+            Color::Cyan => "rgb(0,255,255)",
+            Color::Yellow => "rgb(255,255,0)",
+        }
+    }
+
+    pub fn ansi(&self) -> &'static str {
+        match self {
+            Color::Red => "\x1b[31m",
+            Color::Green => "\x1b[32m",
+            Color::Gray => "\x1b[90m",
+            Color::Cyan => "\x1b[36m",
+            Color::Yellow => "\x1b[33m",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub text: String,
+    pub color: Option<Color>, //None is the default text color
+}
+
+pub fn parse_markup(string: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut stack : Vec<Color> = Vec::new();
+    let mut text = String::new();
+
+    let mut rest = string;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        if c == '{' {
+            match Color::TAGS.iter().find(|(tag, _)| rest.starts_with(tag)) {
+                Some((tag, color)) => {
+                    push_span(&mut spans, &mut text, stack.last().cloned());
+                    stack.push(*color);
+                    rest = &rest[tag.len()..];
+                    continue;
+                },
+                None => {},
+            }
+        } else if c == '}' && !stack.is_empty() {
+            push_span(&mut spans, &mut text, stack.pop());
+            continue;
+        }
+        text.push(c);
+    }
+    push_span(&mut spans, &mut text, stack.last().cloned());
+
+    return spans;
+}
+
+fn push_span(spans: &mut Vec<Span>, text: &mut String, color: Option<Color>) {
+    if !text.is_empty() {
+        spans.push(Span{ text: text.clone(), color: color });
+        text.clear();
+    }
+}
+
+fn escape_html(string: &str) -> String {
+    string.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+///-----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub turn: i64,
+    pub time: String, //HH:MM:SS
+    pub spans: Vec<Span>,
+}
+
+impl LogEntry {
+    pub fn new(turn: i64, time: String, string: &str) -> LogEntry {
+        LogEntry{ turn: turn, time: time, spans: parse_markup(string) }
+    }
+
+    pub fn plain(&self) -> String {
+        self.spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    pub fn html(&self) -> String {
+        let mut ret = String::new();
+        for s in self.spans.iter() {
+            let mut text = escape_html(&s.text);
+            //country flag
+            text = text.replace("🇪 🇸", "<img src=\"./es.svg\" alt=\"\" style=\"height:14px\">");
+            match s.color {
+                Some(color) => ret += &format!("<span style=\"color:{}\">{}</span>", color.css(), text),
+                None => ret += &text,
+            }
+        }
+        return ret;
+    }
+
+    pub fn ansi(&self) -> String {
+        let mut ret = String::new();
+        for s in self.spans.iter() {
+            match s.color {
+                Some(color) => ret += &format!("{}{}\x1b[0m", color.ansi(), s.text),
+                None => ret += &s.text,
+            }
+        }
+        return ret;
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum LogFormat {
+    Plain,
+    Html,
+    Ansi,
+}
+
+#[derive(Default)]
+pub struct MessageLog {
+    entries: VecDeque<LogEntry>,
+}
+
+//the log history screen doesn't need more
+const MAX_ENTRIES: usize = 1000;
+
+impl MessageLog {
+    pub fn new() -> MessageLog {
+        Default::default()
+    }
+
+    pub fn push(&mut self, entry: LogEntry) {
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn entries(&self) -> &VecDeque<LogEntry> {
+        &self.entries
+    }
+
+    pub fn render(&self, format: LogFormat) -> String {
+        let lines : Vec<String> = self.entries.iter().map(|e| match format {
+            LogFormat::Plain => format!("[{}] {}", e.time, e.plain()),
+            LogFormat::Html => format!("<div>[{}] {}</div>", e.time, e.html()),
+            LogFormat::Ansi => format!("[{}] {}", e.time, e.ansi()),
+        }).collect();
+        return lines.join("\n");
+    }
+}
@@ -0,0 +1,198 @@
+//where the simulation's output ends up - the browser DOM or plain memory (native runs, tests)
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast; // for dyn_into
+
+use super::log;
+use super::{Conversation, LogEntry};
+
+// &self because the simulation reports from places where it only has a shared borrow (e.g. inside ECS queries)
+pub trait GameOutput {
+    fn message(&self, entry: &LogEntry);
+    fn describe(&self, string: &str);
+    fn open_vendor(&self);
+    fn open_conversation(&self, conv: &Conversation, npc_name: Option<String>);
+}
+
+///-----------------------------------------------------------------------------
+//TODO: shuffle all or most of this to JS because Rust is clunky when it comes to DOM... :/
+//using web_sys here because I am not too sure on how to pass strings to custom JS
+pub struct WebOutput {}
+
+impl GameOutput for WebOutput {
+    fn message(&self, entry: &LogEntry) {
+        let window = web_sys::window().expect("global window does not exists");
+        let document = window.document().expect("expecting a document on window");
+
+        let messages = document.get_element_by_id("messages").unwrap();
+
+        let log_history = document.get_element_by_id("log-history").unwrap();
+
+        let line = document.create_element("div").unwrap();
+        let html = entry.html();
+        line.set_inner_html(&html); //wants &str
+        messages.append_child(&line).unwrap(); //implicitly converts to Node
+
+        //clone to place a copy
+        let ln = line.clone_node().unwrap(); //Rust version for some reason doesn't have the deep parameter?
+        ln.dyn_ref::<web_sys::Element>().unwrap().set_inner_html(&html); //because clone_node doesn't do it for some reason
+        log_history.append_child(&ln).unwrap();
+
+        //axe the first if more than 5
+        while messages.child_element_count() > 5 {
+            messages.remove_child(&messages.first_element_child().unwrap()); //implicit conversion
+        }
+    }
+
+    fn describe(&self, string: &str) {
+        let window = web_sys::window().expect("global window does not exists");
+        let document = window.document().expect("expecting a document on window");
+
+        let desc = document.get_element_by_id("game-desc").unwrap();
+
+        desc.set_inner_html(string);
+    }
+
+    fn open_vendor(&self) {
+        let window = web_sys::window().expect("global window does not exists");
+        let document = window.document().expect("expecting a document on window");
+        let vendor = document.get_element_by_id("vendor").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
+        vendor.class_list().toggle("visible");
+    }
+
+    fn open_conversation(&self, conv: &Conversation, npc_name: Option<String>) {
+        //display convo
+        let window = web_sys::window().expect("global window does not exists");
+        let document = window.document().expect("expecting a document on window");
+        let view = document.get_element_by_id("conversation").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
+
+        //the img is there to mark language being spoken by NPC
+        let text = &format!("<div> <img src=\"./es.svg\" alt=\"\" style=\"height:14px\"> {} </div>", conv.text);
+        let mut replies = "".to_string();
+        for (i, a) in conv.answers.iter().enumerate() {
+            let tmp = format!("<button id=conv-id-{}>{}</button> <span style=\"color:rgb(0,255,0)\"> {} </span>", i, i, a);
+            replies = format!("{} \n {}", replies, tmp);
+        }
+        //append a question for their name
+        if npc_name.is_some() {
+            let tmp = format!("<button id=conv-id-{}>{}</button> <span style=\"color:rgb(0,255,0)\"> Cual es tu nombre?</span>", conv.answers.len(), conv.answers.len());
+            replies = format!("{} \n {}", replies, tmp);
+        }
+
+        // mark language for the player, too
+        view.set_inner_html(&format!("{} \n <img src=\"./es.svg\" alt=\"\" style=\"height:14px\"> {}", text, replies));
+
+
+        //basic interactivity
+        for (i,_a) in conv.answers.iter().enumerate() {
+            //closure
+            //needs move due to i being used
+            let click_handle =  Closure::wrap(Box::new(move || {
+                log!("Clicked button for answer id {}", i);
+
+                //close the menu for now
+                //get the damned thing by ourselves to avoid 'value moved'
+                let window = web_sys::window().expect("global window does not exists");
+                let document = window.document().expect("expecting a document on window");
+                let view = document.get_element_by_id("conversation").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
+                view.class_list().toggle("visible");
+            }) as Box<dyn FnMut()>);
+
+            let id = &format!("conv-id-{}", i);
+            let but = document.get_element_by_id(id).unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
+            but.set_onclick(Some(click_handle.as_ref().unchecked_ref()));
+
+            //avoid memleak on Rust side
+            click_handle.forget();
+        }
+
+        // handle asking their name
+        if let Some(name) = npc_name {
+            // https://rustwasm.github.io/wasm-bindgen/api/wasm_bindgen/closure/struct.Closure.html
+            //It must be 'static, aka no stack references (use the move keyword).
+            let click_handle_b = Closure::wrap(Box::new(move || {
+                log!("{}", &format!("Clicked name question, {}", name));
+
+                //redraw the window
+                //get the damned thing by ourselves to avoid 'value moved'
+                let window = web_sys::window().expect("global window does not exists");
+                let document = window.document().expect("expecting a document on window");
+                let view = document.get_element_by_id("conversation").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
+
+                //the img is there to mark language being spoken by NPC
+                let text = &format!("<div> <img src=\"./es.svg\" alt=\"\" style=\"height:14px\"> Me llamo {} </div>", name);
+                view.set_inner_html(text);
+
+            }) as Box<dyn FnMut()>);
+
+
+            let id = &format!("conv-id-{}", conv.answers.len());
+            let but = document.get_element_by_id(id).unwrap().dyn_into::<web_sys::HtmlElement>().unwrap();
+            // uses `as_ref()` to get a `JsValue`
+            // from our `Closure` which is then converted to a `&Function`
+            // using the `JsCast::unchecked_ref` function.
+            but.set_onclick(Some(click_handle_b.as_ref().unchecked_ref()));
+
+
+            //avoid memleak on Rust side
+            click_handle_b.forget();
+        }
+
+        view.class_list().toggle("visible");
+    }
+}
+
+///-----------------------------------------------------------------------------
+// keeps everything in memory, so that the game can run without a browser
+// clones share the same buffers, so keep a clone around to inspect what the game said
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemoryOutput {
+    messages: Rc<RefCell<Vec<String>>>,
+    description: Rc<RefCell<String>>,
+    screens: Rc<RefCell<Vec<String>>>,
+}
+
+#[cfg(test)]
+impl MemoryOutput {
+    pub fn new() -> MemoryOutput {
+        Default::default()
+    }
+
+    //plain text, without the markup
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.borrow().clone()
+    }
+
+    pub fn description(&self) -> String {
+        self.description.borrow().clone()
+    }
+
+    // vendor/conversation screens opened so far
+    pub fn screens(&self) -> Vec<String> {
+        self.screens.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+impl GameOutput for MemoryOutput {
+    fn message(&self, entry: &LogEntry) {
+        self.messages.borrow_mut().push(entry.plain());
+    }
+
+    fn describe(&self, string: &str) {
+        *self.description.borrow_mut() = string.to_string();
+    }
+
+    fn open_vendor(&self) {
+        self.screens.borrow_mut().push("vendor".to_string());
+    }
+
+    fn open_conversation(&self, conv: &Conversation, _npc_name: Option<String>) {
+        self.screens.borrow_mut().push(format!("conversation: {}", conv.text));
+    }
+}
@@ -0,0 +1,308 @@
+//named save slots, each with a bit of metadata so that the player can tell them apart
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde::{Serialize, Deserialize};
+
+use hecs::Entity;
+
+
+use super::log;
+use super::{Universe, Player, GameState, CombatStats, Money, GameEvent};
+use super::data_loader::ZoneType;
+use super::saveload;
+use super::saveload::SaveFormat;
+
+// where the slots end up - the browser's localStorage or plain memory (native runs, tests)
+pub trait SaveStorage {
+    fn get(&self, key: &str) -> Option<String>;
+    fn set(&self, key: &str, value: &str) -> bool;
+    fn remove(&self, key: &str);
+}
+
+pub struct WebStorage {}
+
+impl WebStorage {
+    fn storage(&self) -> Option<web_sys::Storage> {
+        let window = web_sys::window()?;
+        //private browsing and the like can refuse
+        window.local_storage().ok()?
+    }
+}
+
+impl SaveStorage for WebStorage {
+    fn get(&self, key: &str) -> Option<String> {
+        self.storage()?.get_item(key).ok()?
+    }
+
+    fn set(&self, key: &str, value: &str) -> bool {
+        match self.storage() {
+            //fails if the storage quota is exceeded
+            Some(storage) => storage.set_item(key, value).is_ok(),
+            None => false,
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        if let Some(storage) = self.storage() {
+            storage.remove_item(key);
+        }
+    }
+}
+
+// clones share the same data
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    data: Rc<RefCell<HashMap<String, String>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        Default::default()
+    }
+}
+
+impl SaveStorage for MemoryStorage {
+    fn get(&self, key: &str) -> Option<String> {
+        self.data.borrow().get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) -> bool {
+        self.data.borrow_mut().insert(key.to_string(), value.to_string());
+        true
+    }
+
+    fn remove(&self, key: &str) {
+        self.data.borrow_mut().remove(key);
+    }
+}
+
+///-----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotInfo {
+    pub name: String,
+    pub turns: i64,
+    pub time: String, //in-game, HH:MM:SS
+    pub hp: i32,
+    pub max_hp: i32,
+    pub money: f32,
+    pub location: String,
+    pub timestamp: String, //real time, RFC 3339
+    pub seed: u64,
+}
+
+//when the game saves by itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutosavePolicy {
+    pub every_turns: i64, //0 turns it off
+    pub before_long_waits: bool, //resting and waiting simulate hours at once
+    pub on_new_area: bool, //after taking the stairs to another level
+    pub slots: u32, //autosave-1 .. autosave-N, the oldest one gets overwritten; 0 turns autosaving off
+}
+
+impl Default for AutosavePolicy {
+    fn default() -> AutosavePolicy {
+        //every 10 in-game minutes
+        AutosavePolicy{ every_turns: 600, before_long_waits: true, on_new_area: true, slots: 3 }
+    }
+}
+
+#[derive(Default)]
+pub struct Autosave {
+    pub policy: AutosavePolicy,
+    last_turn: i64,
+    next_slot: u32,
+    new_area: bool, //saved on the next tick, once the move is over
+}
+
+impl Autosave {
+    pub fn on_event(&mut self, event: &GameEvent) {
+        if let GameEvent::LevelChanged{..} = event {
+            self.new_area = true;
+        }
+    }
+}
+
+//the list of slots lives under its own key, so that listing doesn't need to load every save
+const SLOT_LIST_KEY: &str = "neon-twilight-slots";
+
+fn slot_key(name: &str) -> String {
+    format!("neon-twilight-slot:{}", name)
+}
+
+//compact saves are stored base64-encoded, behind this prefix (JSON saves start with '{')
+const COMPACT_PREFIX: &str = "b64:";
+
+//None if the slot holds a JSON save
+pub fn compact_from_slot(data: &str) -> Option<Vec<u8>> {
+    if !data.starts_with(COMPACT_PREFIX) {
+        return None;
+    }
+    //garbage decodes to nothing, which then fails to load with a message
+    Some(base64::decode(&data[COMPACT_PREFIX.len()..]).unwrap_or_default())
+}
+
+impl Universe {
+    pub fn slot_list(&self) -> Vec<SlotInfo> {
+        match self.storage.get(SLOT_LIST_KEY) {
+            Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+            None => Vec::new(),
+        }
+    }
+
+    fn write_slot_list(&self, slots: &Vec<SlotInfo>) -> bool {
+        match serde_json::to_string(slots) {
+            Ok(json) => self.storage.set(SLOT_LIST_KEY, &json),
+            Err(_) => false,
+        }
+    }
+
+    pub fn slot_info(&self, name: &str) -> SlotInfo {
+        let mut info = SlotInfo{ name: name.to_string(), turns: 0, time: "".to_string(), hp: 0, max_hp: 0, money: 0.0,
+            location: self.location_name(), timestamp: chrono::Utc::now().to_rfc3339(), seed: self.seed };
+
+        //get player entity
+        let mut play: Option<Entity> = None;
+        for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
+            play = Some(id);
+        }
+        match play {
+            Some(entity) => {
+                if let Ok(gs) = self.ecs_world.get::<GameState>(entity) {
+                    info.turns = gs.turns;
+                    info.time = gs.clock().format("%H:%M:%S").to_string();
+                }
+                if let Ok(stats) = self.ecs_world.get::<CombatStats>(entity) {
+                    info.hp = stats.hp;
+                    info.max_hp = stats.max_hp;
+                }
+                if let Ok(money) = self.ecs_world.get::<Money>(entity) {
+                    info.money = money.money;
+                }
+            },
+            None => {},
+        }
+        return info;
+    }
+
+    //short, for the slot list (the full description is in text_description)
+    fn location_name(&self) -> String {
+        //cellars, sewers and such go by their own name
+        if self.level != 0 {
+            let name = self.level_name(self.level);
+            let mut chars = name.chars();
+            return chars.next().map_or(String::new(), |c| c.to_uppercase().collect::<String>() + chars.as_str());
+        }
+        let name = match ZoneType::of(&self.map, self.player_position) {
+            ZoneType::Indoors => "Inside a building",
+            ZoneType::Streets => "Town streets",
+            ZoneType::Outskirts => "Outskirts of the town",
+        };
+        return name.to_string();
+    }
+
+    // overwrites a slot with the same name
+    pub fn save_slot(&self, name: &str) -> bool {
+        let data = match self.save_format {
+            SaveFormat::Json => saveload::save_game(self),
+            //localStorage only holds strings
+            SaveFormat::Compact => {
+                let bytes = saveload::save_game_compact(self);
+                if bytes.is_empty() { "".to_string() } else { format!("{}{}", COMPACT_PREFIX, base64::encode(&bytes)) }
+            },
+        };
+        if data.is_empty() || !self.storage.set(&slot_key(name), &data) {
+            log!("{}", &format!("Could not write save slot {}", name));
+            return false;
+        }
+
+        let mut slots = self.slot_list();
+        slots.retain(|s| s.name != name);
+        slots.push(self.slot_info(name));
+        return self.write_slot_list(&slots);
+    }
+
+    //rotates through the autosave slots
+    pub fn autosave(&self, reason: &str) -> bool {
+        let name = {
+            let mut auto = self.autosave.borrow_mut();
+            if auto.policy.slots == 0 {
+                return false;
+            }
+            let name = format!("autosave-{}", auto.next_slot % auto.policy.slots + 1);
+            auto.next_slot = (auto.next_slot + 1) % auto.policy.slots;
+            auto.last_turn = self.get_time();
+            name
+        };
+        log!("{}", &format!("Autosaving to {} ({})", name, reason));
+        return self.save_slot(&name);
+    }
+
+    //called from calendar_time, so after every player action
+    pub fn autosave_tick(&self) {
+        let turns = self.get_time();
+        let (due, new_area) = {
+            let mut auto = self.autosave.borrow_mut();
+            let due = auto.policy.every_turns > 0 && turns - auto.last_turn >= auto.policy.every_turns;
+            let new_area = std::mem::replace(&mut auto.new_area, false) && auto.policy.on_new_area;
+            (due, new_area)
+        };
+        if due {
+            self.autosave("turns");
+        } else if new_area {
+            self.autosave("new area");
+        }
+    }
+
+    //a panic halfway through 8 hours of simulation shouldn't cost the run
+    pub fn autosave_before_wait(&self) {
+        if self.autosave.borrow().policy.before_long_waits {
+            self.autosave("long wait");
+        }
+    }
+
+    //the counters start over when a game starts or gets loaded
+    pub fn reset_autosave(&self) {
+        let mut auto = self.autosave.borrow_mut();
+        auto.last_turn = self.get_time();
+        auto.new_area = false;
+    }
+
+    //puts a save from elsewhere (the old single browser save) into a slot as it is, it gets migrated when loaded
+    pub fn import_slot(&self, name: &str, data: &str) -> bool {
+        let save = match compact_from_slot(data) {
+            Some(bytes) => saveload::parse_compact(&bytes),
+            None => saveload::parse_save(data),
+        };
+        let info = match save {
+            Ok(save) => save.slot_info(name),
+            Err(e) => {
+                log!("{}", &format!("Could not import save into {}: {}", name, e));
+                return false;
+            }
+        };
+        if !self.storage.set(&slot_key(name), data) {
+            log!("{}", &format!("Could not write save slot {}", name));
+            return false;
+        }
+
+        let mut slots = self.slot_list();
+        slots.retain(|s| s.name != name);
+        slots.push(info);
+        return self.write_slot_list(&slots);
+    }
+
+    pub fn load_slot_data(&self, name: &str) -> Option<String> {
+        self.storage.get(&slot_key(name))
+    }
+
+    pub fn remove_slot(&self, name: &str) -> bool {
+        let mut slots = self.slot_list();
+        let count = slots.len();
+        slots.retain(|s| s.name != name);
+        self.storage.remove(&slot_key(name));
+        return slots.len() != count && self.write_slot_list(&slots);
+    }
+}
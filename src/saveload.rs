@@ -1,17 +1,31 @@
 use super::log;
-use super::{Universe, 
+use super::{Universe, GameRng, PersistentId,
     Point, Player, Renderable, GameState, Needs, Money, 
-    AI, Vendor, CombatStats, Faction, 
-    Item, InBackpack, Consumable, ProvidesHealing, ProvidesFood, ProvidesQuench, Equippable, MeleeBonus, Equipped};
+    AI, Speed, Vendor, CombatStats, Faction, 
+    Item, InBackpack, Consumable, ProvidesHealing, ProvidesFood, ProvidesQuench, Equippable, MeleeBonus, Equipped,
+    Attributes, NPCName, Conversation, Path, Asleep, DefenseBonus, ToRemove, SpawnedFrom, Level};
 
 //save/load
 use serde::{Serialize, Deserialize};
-use serde_json::json;
+
+use wasm_bindgen::prelude::*;
+use serde_json::{json, Value};
+
+use std::fmt;
+use std::collections::HashMap;
+
+use hecs::Entity;
+
+use super::map::Map;
+use super::save_slots::SlotInfo;
+
+use rand::SeedableRng;
 
 // what it says on the tin
+//every component except WantsToUseItem/WantsToDropItem, those only live during a single action
 #[derive(Serialize, Deserialize)]
 pub struct SaveData {
-    entity: u64, //because Entity cannot be serialized by serde
+    entity: u64, //PersistentId, because Entity cannot be serialized by serde (and changes on load)
     name: String,
     point: Option<Point>,
     render: Option<Renderable>,
@@ -20,6 +34,7 @@ pub struct SaveData {
     needs: Option<Needs>,
     money: Option<Money>,
     ai: Option<AI>,
+    speed: Option<Speed>,
     vendor: Option<Vendor>,
     combat: Option<CombatStats>,
     faction: Option<Faction>,
@@ -32,21 +47,50 @@ pub struct SaveData {
     equippable: Option<Equippable>,
     meleebonus: Option<MeleeBonus>,
     equip: Option<Equipped>,
+    #[serde(default)] //not in older saves
+    attributes: Option<Attributes>,
+    #[serde(default)] //not in older saves
+    npc_name: Option<NPCName>,
+    #[serde(default)] //not in older saves
+    conversation: Option<Conversation>,
+    #[serde(default)] //not in older saves
+    path: Option<Path>,
+    #[serde(default)] //not in older saves
+    asleep: Option<Asleep>,
+    #[serde(default)] //not in older saves
+    defense: Option<DefenseBonus>,
+    #[serde(default)] //not in older saves
+    to_remove: Option<ToRemove>,
+    #[serde(default)] //not in older saves
+    spawned_from: Option<SpawnedFrom>,
+    #[serde(default)] //not in older saves, where everything is in the town
+    level: Option<Level>,
 }
 
 ///---------------------------------------------------------------------------------------------------
 //save/load
-pub fn save_game(u: &Universe) -> String {
+fn build_save(u: &Universe) -> SaveFile {
     log!("Saving game...");
     //iterate over all entities
     let entities = u.ecs_world.iter().map(|(id, _)| id).collect::<Vec<_>>();
     let mut save_datas : Vec<SaveData> = Vec::new();
 
+    //entities spawned since ids were last assigned get one here
+    let mut next_id = u.next_persistent_id;
+    let mut ids : HashMap<Entity, u64> = HashMap::new();
+    for e in entities.iter() {
+        let id = match u.ecs_world.get::<PersistentId>(*e) {
+            Ok(pid) => pid.0,
+            Err(_) => { next_id += 1; next_id - 1 },
+        };
+        ids.insert(*e, id);
+    }
+
     for e in entities {
         //note to self: JSON macro doesn't work with conditionals
         //so we need an intermediate struct
         let mut saved = SaveData{
-            entity: e.to_bits(),
+            entity: ids[&e],
             point: None,
             render: None,
             name: "".to_string(), //because props don't have names //u.ecs_world.get::<String>(e).unwrap().to_string(),
@@ -54,6 +98,7 @@ pub fn save_game(u: &Universe) -> String {
             gamestate: None,
             needs: None,
             ai: None,
+            speed: None,
             money: None,
             faction: None,
             vendor: None,
@@ -67,6 +112,15 @@ pub fn save_game(u: &Universe) -> String {
             equippable: None,
             meleebonus: None,
             equip : None,
+            attributes: None,
+            npc_name: None,
+            conversation: None,
+            path: None,
+            asleep: None,
+            defense: None,
+            to_remove: None,
+            spawned_from: None,
+            level: None,
         };
 
         //log!("{:?}", e);
@@ -96,6 +150,9 @@ pub fn save_game(u: &Universe) -> String {
         if u.ecs_world.get::<AI>(e).is_ok(){
             saved.ai = Some(*u.ecs_world.get::<AI>(e).unwrap());
         }
+        if u.ecs_world.get::<Speed>(e).is_ok(){
+            saved.speed = Some(*u.ecs_world.get::<Speed>(e).unwrap());
+        }
         if u.ecs_world.get::<Needs>(e).is_ok(){
             saved.needs = Some(*u.ecs_world.get::<Needs>(e).unwrap());
         }
@@ -136,125 +193,528 @@ pub fn save_game(u: &Universe) -> String {
             saved.meleebonus = Some(*u.ecs_world.get::<MeleeBonus>(e).unwrap());
         }
         if u.ecs_world.get::<Equipped>(e).is_ok() {
-            saved.equip = Some(*u.ecs_world.get::<Equipped>(e).unwrap()); 
+            let mut equip = *u.ecs_world.get::<Equipped>(e).unwrap();
+            //refer to the owner by their persistent id
+            match ids.get(&hecs::Entity::from_bits(equip.owner)) {
+                Some(id) => {
+                    equip.owner = *id;
+                    saved.equip = Some(equip);
+                },
+                None => log!("{}", &format!("Not saving equipped state of {:?}, owner is gone", e)),
+            }
+        }
+        if u.ecs_world.get::<Attributes>(e).is_ok() {
+            saved.attributes = Some(*u.ecs_world.get::<Attributes>(e).unwrap());
+        }
+        if u.ecs_world.get::<NPCName>(e).is_ok() {
+            saved.npc_name = Some((*u.ecs_world.get::<NPCName>(e).unwrap()).clone());
+        }
+        if u.ecs_world.get::<Conversation>(e).is_ok() {
+            saved.conversation = Some((*u.ecs_world.get::<Conversation>(e).unwrap()).clone());
+        }
+        if u.ecs_world.get::<Path>(e).is_ok() {
+            saved.path = Some((*u.ecs_world.get::<Path>(e).unwrap()).clone());
+        }
+        if u.ecs_world.get::<Asleep>(e).is_ok() {
+            saved.asleep = Some(*u.ecs_world.get::<Asleep>(e).unwrap());
+        }
+        if u.ecs_world.get::<DefenseBonus>(e).is_ok() {
+            saved.defense = Some(*u.ecs_world.get::<DefenseBonus>(e).unwrap());
+        }
+        if u.ecs_world.get::<ToRemove>(e).is_ok() {
+            saved.to_remove = Some(*u.ecs_world.get::<ToRemove>(e).unwrap());
+        }
+        if u.ecs_world.get::<SpawnedFrom>(e).is_ok() {
+            saved.spawned_from = Some((*u.ecs_world.get::<SpawnedFrom>(e).unwrap()).clone());
+        }
+        if u.ecs_world.get::<Level>(e).is_ok() {
+            saved.level = Some(*u.ecs_world.get::<Level>(e).unwrap());
         }
 
         save_datas.push(saved);
     }
-    //'r' stands for Result
-    let json_r = serde_json::to_string(&save_datas);
-    log!("JSON: {:?} ", json_r);
+    SaveFile{
+        format_version: FORMAT_VERSION,
+        game_version: env!("CARGO_PKG_VERSION").to_string(),
+        seed: u.seed,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        entities: save_datas,
+        levels: u.all_levels(),
+        level: u.level,
+        //RNG state, so that the game continues exactly as it would have
+        rng: Some(u.rng.clone()),
+    }
+}
+
+pub fn save_game(u: &Universe) -> String {
+    let save = build_save(u);
+    match serde_json::to_string(&save) {
+        Ok(json) => json,
+        Err(e) => {
+            log!("{}", &format!("Failed to save: {}", e));
+            "".to_string()
+        }
+    }
+}
+
+// upgrades the save one version at a time, then loads it
+// nothing in the universe changes unless the whole save is fine
+pub fn load_save(u: &mut Universe, data: String) -> Result<(), SaveError> {
+    log!("Rust received loaded data {}", data);
+    let save = parse_save(&data)?;
+    apply_save(u, save);
+    return Ok(());
+}
+
+pub fn load_save_compact(u: &mut Universe, data: &[u8]) -> Result<(), SaveError> {
+    log!("{}", &format!("Rust received compact save, {} bytes", data.len()));
+    let save = parse_compact(data)?;
+    apply_save(u, save);
+    return Ok(());
+}
+
+fn apply_save(u: &mut Universe, save: SaveFile) {
+    // the player's seed is the source of truth, older saves only have it there
+    u.seed = save.seed;
+
+    //loading replaces the whole game
+    u.ecs_world.clear();
+    //persistent id -> the freshly spawned entity
+    let mut spawned : HashMap<u64, Entity> = HashMap::new();
+
+    for e in save.entities {
+        //log!("Ent from save: {:?}", e);
+        //log!("{}", &format!("Ent from save: {} {} {:?} {:?} {:?} {:?} {:?}", e.entity, e.name, e.render, e.point, e.item, e.backpack, e.equip));
+            
+        //build our entity from pieces listed
+        let mut builder = hecs::EntityBuilder::new();
+        builder.add(PersistentId(e.entity));
+        //props don't have names
+        if !e.name.is_empty() {
+            builder.add(e.name);
+        }
+        if e.render.is_some(){
+            builder.add(e.render.unwrap());
+        }
+        if e.player.is_some(){
+            builder.add(e.player.unwrap());
+            //the player's position lives in the Universe, not in a Point
+            let point = e.point.unwrap();
+            u.player_position = save.levels[save.level].xy_idx(point.x, point.y);
+        } else if e.point.is_some(){
+            builder.add(e.point.unwrap());
+        }
+        if e.gamestate.is_some(){
+            builder.add(e.gamestate.unwrap());
+        }
+        if e.needs.is_some(){
+            builder.add(e.needs.unwrap());
+        }
+        if e.ai.is_some(){
+            builder.add(e.ai.unwrap());
+        }
+        if e.speed.is_some(){
+            builder.add(e.speed.unwrap());
+        }
+        if e.money.is_some() {
+            builder.add(e.money.unwrap());
+        }
+        if e.faction.is_some() {
+            builder.add(e.faction.unwrap());
+        }
+        if e.vendor.is_some() {
+            builder.add(e.vendor.unwrap());
+        }
+        if e.combat.is_some(){
+            builder.add(e.combat.unwrap());
+        }
+        if e.item.is_some(){
+            builder.add(e.item.unwrap());
+        }
+        if e.backpack.is_some(){
+            builder.add(e.backpack.unwrap());
+        }
+        if e.consumable.is_some(){
+            builder.add(e.consumable.unwrap());
+        }
+        if e.heals.is_some(){
+            builder.add(e.heals.unwrap());
+        }
+        if e.food.is_some(){
+            builder.add(e.food.unwrap());
+        }
+        if e.quench.is_some(){
+            builder.add(e.quench.unwrap());
+        }
+        if e.equippable.is_some(){
+            builder.add(e.equippable.unwrap());
+        }
+        if e.meleebonus.is_some(){
+            builder.add(e.meleebonus.unwrap());
+        }
+        if e.equip.is_some(){
+            builder.add(e.equip.unwrap());
+        }
+        if e.attributes.is_some(){
+            builder.add(e.attributes.unwrap());
+        }
+        if e.npc_name.is_some(){
+            builder.add(e.npc_name.unwrap());
+        }
+        if e.conversation.is_some(){
+            builder.add(e.conversation.unwrap());
+        }
+        if e.path.is_some(){
+            builder.add(e.path.unwrap());
+        }
+        if e.asleep.is_some(){
+            builder.add(e.asleep.unwrap());
+        }
+        if e.defense.is_some(){
+            builder.add(e.defense.unwrap());
+        }
+        if e.to_remove.is_some(){
+            builder.add(e.to_remove.unwrap());
+        }
+        if e.spawned_from.is_some(){
+            builder.add(e.spawned_from.unwrap());
+        }
+        if e.level.is_some(){
+            builder.add(e.level.unwrap());
+        }
+
+        // spawn based on loaded data
+        let ent = u.ecs_world.spawn(builder.build());
+        spawned.insert(e.entity, ent);
+    }
+
+    //remap references to other entities from persistent ids to the new entities
+    let mut orphans : Vec<Entity> = Vec::new();
+    for (id, equipped) in u.ecs_world.query::<&mut Equipped>().iter() {
+        match spawned.get(&equipped.owner) {
+            Some(owner) => equipped.owner = owner.to_bits(),
+            None => orphans.push(id),
+        }
+    }
+    for id in orphans {
+        log!("{}", &format!("Owner of equipped {:?} is not in the save, unequipping", id));
+        u.ecs_world.remove_one::<Equipped>(id);
+    }
+    u.next_persistent_id = spawned.keys().max().map_or(0, |id| id + 1);
+
+    u.set_levels(save.levels, save.level);
+
+    //older saves don't have the RNG state, so just start from the seed again
+    u.rng = match save.rng {
+        Some(rng) => rng,
+        None => GameRng::seed_from_u64(u.seed),
+    };
+}
+
+///---------------------------------------------------------------------------------------------------
+//format versions:
+// 0 - a JSON array of entities + " \nmap:" + map JSON (+ " \nrng:" + RNG JSON), no version anywhere
+// 1 - SaveFile envelope
+// 2 - entities (and references to them, e.g. Equipped.owner) are PersistentIds instead of Entity bits
+// 3 - entities remember the prefab they were spawned from
+// 4 - a list of levels (and the player's one) instead of a single map, entities know their level
+//(the player's GameState got the scenario's start time without a new version, older saves default to 08:00)
+pub const FORMAT_VERSION: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveFile {
+    pub format_version: u32,
+    pub game_version: String,
+    pub seed: u64,
+    pub timestamp: String, //RFC 3339, UTC
+    pub entities: Vec<SaveData>,
+    pub levels: Vec<Map>,
+    pub level: usize, //the player's
+    pub rng: Option<GameRng>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SaveError {
+    Empty,
+    Malformed(String), //not JSON, or not what we expected
+    MissingSection(String),
+    TooNew(u32), //saved by a newer version of the game
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Empty => write!(f, "the save is empty"),
+            SaveError::Malformed(e) => write!(f, "the save is malformed ({})", e),
+            SaveError::MissingSection(s) => write!(f, "the save has no {} section", s),
+            SaveError::TooNew(v) => write!(f, "the save is format version {}, this game only knows up to {}", v, FORMAT_VERSION),
+        }
+    }
+}
 
+// each one upgrades from the version equal to its index
+const MIGRATIONS: [fn(Value) -> Result<Value, SaveError>; 4] = [
+    migrate_0_to_1,
+    migrate_1_to_2,
+    migrate_2_to_3,
+    migrate_3_to_4,
+];
 
-    //map data
-    let json_r2 = serde_json::to_string(&u.map);
-    log!("JSON 2: {:?}", json_r2);
+pub fn parse_save(data: &str) -> Result<SaveFile, SaveError> {
+    let data = data.trim();
+    if data.is_empty() {
+        return Err(SaveError::Empty);
+    }
 
-    //log!("{}", &format!("{}", serde_json::to_string(&u.player_position).unwrap()));
-    // extract String from Result
-    if json_r.is_ok() && json_r2.is_ok() {
-        //hack because we can't return a tuple or Vec<> of Strings
-        return json_r.unwrap() + " \nmap:" + &json_r2.unwrap();
+    //version 0 isn't valid JSON as a whole
+    let value = if data.starts_with('{') {
+        serde_json::from_str::<Value>(data).map_err(|e| SaveError::Malformed(e.to_string()))?
     } else {
-        return "".to_string();
+        legacy_to_value(data)?
+    };
+
+    return upgrade(value);
+}
+
+//migrates it up to FORMAT_VERSION, whichever format it came in
+fn upgrade(mut value: Value) -> Result<SaveFile, SaveError> {
+    let mut version = match value.get("format_version").and_then(|v| v.as_u64()) {
+        Some(v) => v as u32,
+        None => return Err(SaveError::MissingSection("format_version".to_string())),
+    };
+    if version > FORMAT_VERSION {
+        return Err(SaveError::TooNew(version));
+    }
+    while version < FORMAT_VERSION {
+        log!("{}", &format!("Migrating save from version {}", version));
+        value = MIGRATIONS[version as usize](value)?;
+        version += 1;
     }
+
+    let save : SaveFile = serde_json::from_value(value).map_err(|e| SaveError::Malformed(e.to_string()))?;
+    check_levels(&save)?;
+    Ok(save)
 }
 
-pub fn load_save(u: &mut Universe, data: String) {
-    log!("Rust received loaded data {}", data);
-    // split the string
-    let split : Vec<&str> = data.split(" \nmap:").collect();
-    // for s in split{
-    //     log!("{}", &format!("Split {}", s));
-    // }
-
-    let res =  serde_json::from_str(&split[0]);
-    if res.is_ok() {
-        let ent: Vec<SaveData> = res.unwrap();
-        for e in ent {
-            //log!("Ent from save: {:?}", e);
-            //log!("{}", &format!("Ent from save: {} {} {:?} {:?} {:?} {:?} {:?}", e.entity, e.name, e.render, e.point, e.item, e.backpack, e.equip));
-            
-            //entity handle
-            let ent = hecs::Entity::from_bits(e.entity); //restore
+impl SaveFile {
+    //slot list metadata, for saves that get into a slot without being loaded
+    pub fn slot_info(&self, name: &str) -> SlotInfo {
+        //legacy saves have no timestamp
+        let timestamp = if self.timestamp.is_empty() { chrono::Utc::now().to_rfc3339() } else { self.timestamp.clone() };
+        let mut info = SlotInfo{ name: name.to_string(), turns: 0, time: "".to_string(), hp: 0, max_hp: 0, money: 0.0,
+            location: "Imported".to_string(), timestamp: timestamp, seed: self.seed };
 
-            //build our entity from pieces listed
-            let mut builder = hecs::EntityBuilder::new();
-            builder.add(e.name);
-            if e.render.is_some(){
-                builder.add(e.render.unwrap());
-            }
-            if e.point.is_some(){
-                builder.add(e.point.unwrap());
-            }
-            if e.player.is_some(){
-                builder.add(e.player.unwrap());
-                let point = e.point.unwrap();
-                u.player_position = u.map.xy_idx(point.x, point.y);
-            }
-            if e.gamestate.is_some(){
-                builder.add(e.gamestate.unwrap());
-            }
-            if e.needs.is_some(){
-                builder.add(e.needs.unwrap());
-            }
-            if e.ai.is_some(){
-                builder.add(e.ai.unwrap());
-            }
-            if e.money.is_some() {
-                builder.add(e.money.unwrap());
-            }
-            if e.faction.is_some() {
-                builder.add(e.faction.unwrap());
-            }
-            if e.vendor.is_some() {
-                builder.add(e.vendor.unwrap());
+        if let Some(player) = self.entities.iter().find(|e| e.player.is_some()) {
+            if let Some(gs) = &player.gamestate {
+                info.turns = gs.turns;
+                info.time = gs.clock().format("%H:%M:%S").to_string();
             }
-            if e.combat.is_some(){
-                builder.add(e.combat.unwrap());
+            if let Some(stats) = &player.combat {
+                info.hp = stats.hp;
+                info.max_hp = stats.max_hp;
             }
-            if e.item.is_some(){
-                builder.add(e.item.unwrap());
+            if let Some(money) = &player.money {
+                info.money = money.money;
             }
-            if e.backpack.is_some(){
-                builder.add(e.backpack.unwrap());
-            }
-            if e.consumable.is_some(){
-                builder.add(e.consumable.unwrap());
-            }
-            if e.heals.is_some(){
-                builder.add(e.heals.unwrap());
-            }
-            if e.food.is_some(){
-                builder.add(e.food.unwrap());
-            }
-            if e.quench.is_some(){
-                builder.add(e.quench.unwrap());
-            }
-            if e.equippable.is_some(){
-                builder.add(e.equippable.unwrap());
-            }
-            if e.meleebonus.is_some(){
-                builder.add(e.meleebonus.unwrap());
-            }
-            if e.equip.is_some(){
-                builder.add(e.equip.unwrap());
+        }
+        return info;
+    }
+}
+
+//everyone has to be on a level that's there
+fn check_levels(save: &SaveFile) -> Result<(), SaveError> {
+    let count = save.levels.len();
+    if save.level >= count {
+        return Err(SaveError::Malformed(format!("the player is on level {} of {}", save.level, count)));
+    }
+    for e in save.entities.iter() {
+        if let Some(level) = e.level.filter(|l| l.id >= count) {
+            return Err(SaveError::Malformed(format!("entity {} is on level {} of {}", e.entity, level.id, count)));
+        }
+    }
+    for map in save.levels.iter() {
+        if let Some(exit) = map.exits.iter().find(|x| x.level >= count) {
+            return Err(SaveError::Malformed(format!("stairs lead to level {} of {}", exit.level, count)));
+        }
+    }
+    return Ok(());
+}
+
+//just splits it up, the migration does the rest
+fn legacy_to_value(data: &str) -> Result<Value, SaveError> {
+    let split : Vec<&str> = data.split(" \nmap:").collect();
+    if split.len() < 2 {
+        return Err(SaveError::MissingSection("map".to_string()));
+    }
+    let split_rng : Vec<&str> = split[1].split(" \nrng:").collect();
+
+    let entities : Value = serde_json::from_str(split[0]).map_err(|e| SaveError::Malformed(e.to_string()))?;
+    let map : Value = serde_json::from_str(split_rng[0]).map_err(|e| SaveError::Malformed(e.to_string()))?;
+    let rng = if split_rng.len() > 1 {
+        serde_json::from_str(split_rng[1]).map_err(|e| SaveError::Malformed(e.to_string()))?
+    } else {
+        Value::Null
+    };
+
+    Ok(json!({ "format_version": 0, "entities": entities, "map": map, "rng": rng }))
+}
+
+fn migrate_0_to_1(mut value: Value) -> Result<Value, SaveError> {
+    //the seed only lived in the player's GameState (and not at all in the oldest saves)
+    let seed = value["entities"].as_array()
+        .and_then(|ents| ents.iter().find_map(|e| e["gamestate"]["seed"].as_u64()))
+        .unwrap_or(0);
+
+    value["format_version"] = json!(1);
+    value["game_version"] = json!("unknown");
+    value["seed"] = json!(seed);
+    value["timestamp"] = json!("");
+    Ok(value)
+}
+
+fn migrate_1_to_2(mut value: Value) -> Result<Value, SaveError> {
+    //Entity bits are unique and owners were saved as bits, too, so they work as persistent ids as they are
+    value["format_version"] = json!(2);
+    Ok(value)
+}
+
+//the glyph ids props had before they came from the data file
+const LEGACY_PROPS: [(u64, &str); 3] = [(4, "Table"), (5, "Chair"), (10, "Bed")];
+
+fn migrate_2_to_3(mut value: Value) -> Result<Value, SaveError> {
+    //props are only known by their prefab (e.g. NPCs look for beds), and only had a glyph back then
+    //everyone else just doesn't know their prefab, so data reloads leave them alone
+    if let Some(ents) = value["entities"].as_array_mut() {
+        for e in ents.iter_mut() {
+            let is_prop = e["name"].as_str().map_or(true, |n| n.is_empty()) && e["item"].is_null();
+            let glyph = e["render"]["glyph"].as_u64();
+            if let Some((_, prefab)) = LEGACY_PROPS.iter().find(|p| is_prop && Some(p.0) == glyph) {
+                e["spawned_from"] = json!({ "prefab": prefab });
             }
+        }
+    }
+    value["format_version"] = json!(3);
+    Ok(value)
+}
+
+fn migrate_3_to_4(mut value: Value) -> Result<Value, SaveError> {
+    //there was only the town, and entities without a level are in it
+    //(without a map it fails to load later on, like any other malformed save)
+    if let Some(map) = value.as_object_mut().and_then(|o| o.remove("map")) {
+        value["levels"] = json!([map]);
+    }
+    value["level"] = json!(0);
+    value["format_version"] = json!(4);
+    Ok(value)
+}
+
+///---------------------------------------------------------------------------------------------------
+//what save slots get written as - both formats load the same game
+#[wasm_bindgen]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SaveFormat {
+    Json, //readable, survives format upgrades
+    Compact, //much smaller, for localStorage
+}
+
+//compact format: MAGIC + MessagePack of the same document a JSON save is, with the map layers run-length encoded
+//(they are long runs of the same tile)
+//MessagePack keeps the field names, so it reads back into a serde_json::Value and goes through the same migrations
+pub const COMPACT_MAGIC: &[u8; 4] = b"NTSV";
+
+//(value, how many times in a row)
+pub fn rle_encode<T: PartialEq + Copy>(data: &[T]) -> Vec<(T, u32)> {
+    let mut runs : Vec<(T, u32)> = Vec::new();
+    for &v in data {
+        match runs.last_mut() {
+            Some(run) if run.0 == v => run.1 += 1,
+            _ => runs.push((v, 1)),
+        }
+    }
+    return runs;
+}
 
-            // spawn based on loaded data
-            // automatically despawns any existing entities with the ids
-            u.ecs_world.spawn_at(ent, builder.build());
+//the runs have to add up to exactly size, checked before anything is allocated
+pub fn rle_decode<T: Copy>(runs: &[(T, u32)], size: usize) -> Result<Vec<T>, SaveError> {
+    let total : u64 = runs.iter().map(|r| r.1 as u64).sum();
+    if total != size as u64 {
+        return Err(SaveError::Malformed(format!("expected {} tiles, the runs add up to {}", size, total)));
+    }
+    let mut data = Vec::with_capacity(size);
+    for &(v, count) in runs {
+        for _ in 0..count {
+            data.push(v);
         }
+    }
+    return Ok(data);
+}
 
-        //let current_position = u.map.idx_xy(u.player_position);
-        // // refresh FOV
-        // u.fov_data.clear_fov(); // compute_fov does not clear the existing fov
-        // u.fov.compute_fov(&mut u.fov_data, current_position.0 as usize, current_position.1 as usize, 6, true);
+pub fn save_game_compact(u: &Universe) -> Vec<u8> {
+    let save = build_save(u);
+    let mut value = match serde_json::to_value(&save) {
+        Ok(value) => value,
+        Err(e) => {
+            log!("{}", &format!("Failed to save: {}", e));
+            return Vec::new();
+        }
+    };
+    for (map, level) in save.levels.iter().zip(value["levels"].as_array_mut().unwrap().iter_mut()) {
+        let obj = level.as_object_mut().unwrap();
+        obj.remove("tiles");
+        obj.remove("revealed_tiles");
+        obj.insert("tiles_rle".to_string(), json!(rle_encode(&map.tiles)));
+        obj.insert("revealed_rle".to_string(), json!(rle_encode(&map.revealed_tiles)));
     }
 
-    let res =  serde_json::from_str(&split[1]);
-    if res.is_ok() {
-        let mapa = res.unwrap();
-        u.map = mapa;
+    match rmp_serde::to_vec_named(&value) {
+        Ok(bytes) => {
+            let mut data = COMPACT_MAGIC.to_vec();
+            data.extend(bytes);
+            data
+        },
+        Err(e) => {
+            log!("{}", &format!("Failed to save: {}", e));
+            Vec::new()
+        }
+    }
+}
+
+pub fn parse_compact(data: &[u8]) -> Result<SaveFile, SaveError> {
+    if data.is_empty() {
+        return Err(SaveError::Empty);
     }
-}
\ No newline at end of file
+    if !data.starts_with(COMPACT_MAGIC) {
+        return Err(SaveError::Malformed("not a compact save".to_string()));
+    }
+
+    let mut value : Value = rmp_serde::from_slice(&data[COMPACT_MAGIC.len()..]).map_err(|e| SaveError::Malformed(e.to_string()))?;
+    //wherever the version it was written with keeps its maps
+    if let Some(map) = value.get_mut("map") {
+        expand_map(map)?;
+    }
+    if let Some(levels) = value.get_mut("levels").and_then(|l| l.as_array_mut()) {
+        for map in levels.iter_mut() {
+            expand_map(map)?;
+        }
+    }
+    return upgrade(value);
+}
+
+//back to what the map looks like in a JSON save
+fn expand_map(map: &mut Value) -> Result<(), SaveError> {
+    let size = map["width"].as_u64().unwrap_or(0) * map["height"].as_u64().unwrap_or(0);
+    let obj = match map.as_object_mut() {
+        Some(obj) => obj,
+        None => return Err(SaveError::Malformed("a map isn't an object".to_string())),
+    };
+    let bad = |e: serde_json::Error| SaveError::Malformed(e.to_string());
+    if let Some(runs) = obj.remove("tiles_rle") {
+        let runs : Vec<(u8, u32)> = serde_json::from_value(runs).map_err(bad)?;
+        obj.insert("tiles".to_string(), json!(rle_decode(&runs, size as usize)?));
+    }
+    if let Some(runs) = obj.remove("revealed_rle") {
+        let runs : Vec<(bool, u32)> = serde_json::from_value(runs).map_err(bad)?;
+        obj.insert("revealed_tiles".to_string(), json!(rle_decode(&runs, size as usize)?));
+    }
+    return Ok(());
+}
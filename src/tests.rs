@@ -0,0 +1,1042 @@
+// drives the game natively (no browser), with output going to memory
+use super::*;
+use super::data_loader::DataMaster;
+use rand::Rng;
+
+//the base game is the data file plus the name lists
+fn parse_base(data: &str) -> Result<DataMaster, data_loader::DataLoadError> {
+    with_base(data, Vec::new())
+}
+
+fn with_base(data: &str, mods: Vec<(String, String)>) -> Result<DataMaster, data_loader::DataLoadError> {
+    let mut files = vec![("data.ron".to_string(), data.to_string()), ("names.ron".to_string(), include_str!("../docs/names.ron").to_string())];
+    files.extend(mods);
+    data_loader::parse_layers(&files)
+}
+
+fn test_data() -> DataMaster {
+    parse_base(include_str!("../docs/data.ron")).unwrap()
+}
+
+fn headless_game(seed: u64) -> (Universe, MemoryOutput) {
+    //the vendor and debug console look prefabs up in there
+    data_loader::DATA.lock().unwrap().load(test_data());
+    let out = MemoryOutput::new();
+    let mut u = Universe::with_output(Box::new(out.clone()));
+    u.set_seed(seed);
+    u.game_start(&test_data());
+    (u, out)
+}
+
+// saves are stamped with the time they were made, and entity order depends on ECS internals
+fn save_without_timestamp(u: &Universe) -> serde_json::Value {
+    let mut save : serde_json::Value = serde_json::from_str(&u.save_game()).unwrap();
+    save["timestamp"] = serde_json::Value::Null;
+    save["entities"].as_array_mut().unwrap().sort_by_key(|e| e["entity"].as_u64());
+    save
+}
+
+// a short session that moves, fights if it has to, and lets time pass
+fn play(u: &mut Universe) {
+    for cmd in [Command::MoveLeft, Command::MoveUp, Command::MoveRight, Command::MoveDown, Command::GetItem].iter() {
+        u.process(Some(*cmd));
+    }
+    u.wait(WaitType::Minutes5);
+}
+
+#[test]
+fn game_loop_runs_headless() {
+    let (mut u, out) = headless_game(1);
+    u.on_game_start();
+    assert!(out.messages().iter().any(|m| m.contains("Welcome to Neon Twilight")));
+    assert!(out.description().starts_with("This area appears to be a town"));
+
+    let start_time = u.get_time_of_day();
+    play(&mut u);
+    assert!(u.get_time_of_day() >= start_time + 300);
+    assert!(out.messages().iter().any(|m| m.starts_with("Time: ")));
+}
+
+#[test]
+fn same_seed_same_game() {
+    let (mut a, out_a) = headless_game(1234);
+    let (mut b, out_b) = headless_game(1234);
+    assert_eq!(a.get_tiles(), b.get_tiles());
+    assert_eq!(a.player(), b.player());
+
+    play(&mut a);
+    play(&mut b);
+    assert_eq!(out_a.messages(), out_b.messages());
+    assert_eq!(save_without_timestamp(&a), save_without_timestamp(&b));
+
+    let (c, _) = headless_game(4321);
+    assert_ne!(a.get_tiles(), c.get_tiles());
+}
+
+#[test]
+fn journal_replays_session() {
+    let (mut a, out_a) = headless_game(77);
+    play(&mut a);
+    a.give_item("Medkit".to_string());
+    let journal = a.export_journal();
+
+    let seed = Journal::from_json(&journal).unwrap().seed;
+    let (mut b, out_b) = headless_game(seed);
+    assert!(b.replay_journal(journal.clone()));
+    assert_eq!(out_a.messages(), out_b.messages());
+    assert_eq!(save_without_timestamp(&a), save_without_timestamp(&b));
+    assert_eq!(journal, b.export_journal());
+
+    //wrong seed
+    let (mut c, _) = headless_game(78);
+    assert!(!c.replay_journal(journal));
+}
+
+#[test]
+fn command_queue_processes_in_order() {
+    let (mut u, _out) = headless_game(5);
+    u.queue_command(Command::MoveLeft);
+    u.queue_command(Command::Wait);
+    u.process(Some(Command::MoveRight));
+    let results = u.command_results();
+    assert!(results.starts_with("[{\"command\":\"MoveLeft\",\"result\":\"Consumed\"},{\"command\":\"Wait\""));
+    assert_eq!(u.command_results.len(), 3);
+    assert_eq!(u.commands.len(), 0);
+
+    //dummies don't count
+    u.process(Some(Command::None));
+    assert!(u.command_results.is_empty());
+
+    //journal has the commands, not the moves they caused
+    let journal = Journal::from_json(&u.export_journal()).unwrap();
+    assert_eq!(journal.entries, vec![JournalEntry::Process(Command::MoveLeft), JournalEntry::Process(Command::Wait), JournalEntry::Process(Command::MoveRight)]);
+}
+
+#[test]
+fn dead_player_commands_rejected() {
+    let (mut u, _out) = headless_game(5);
+    let player = u.ecs_world.query::<&Player>().iter().next().unwrap().0;
+    u.ecs_world.get_mut::<CombatStats>(player).unwrap().hp = 0;
+    u.process(Some(Command::MoveLeft));
+    assert_eq!(u.command_results[0].result, CommandResult::Rejected("player is dead".to_string()));
+}
+
+#[test]
+fn actions_cost_time() {
+    let (mut u, _out) = headless_game(9);
+    let player = u.ecs_world.query::<&Player>().iter().next().unwrap().0;
+    let start = u.get_time_of_day();
+    u.end_turn(COST_WALK);
+    assert_eq!(u.get_time_of_day(), start + 1);
+
+    //the starting medkit
+    let medkit = u.inventory_items().into_iter().find(|id| u.inventory_name_for_id(*id) == "Medkit").unwrap();
+    u.use_item_ext(medkit);
+    assert_eq!(u.get_time_of_day(), start + 6);
+    assert_eq!(u.ecs_world.get::<Speed>(player).unwrap().energy, 0);
+
+    //waiting doesn't bank energy for free moves
+    u.wait(WaitType::Minutes5);
+    u.end_turn(COST_WALK);
+    assert_eq!(u.get_time_of_day(), start + 6 + 300 + 1);
+}
+
+#[test]
+fn slow_actors_act_less() {
+    let (mut u, _out) = headless_game(9);
+    //no faction, so it just idles
+    let slow = u.ecs_world.spawn(("Slowpoke".to_string(), Point{x:1, y:1}, AI{}, Speed{speed: 50, energy: 0}));
+    let mut acted = 0;
+    for _ in 0..10 {
+        let before = u.ecs_world.get::<Speed>(slow).unwrap().energy;
+        u.end_turn(COST_WALK);
+        if u.ecs_world.get::<Speed>(slow).unwrap().energy < before + 50 {
+            acted += 1;
+        }
+    }
+    //once on the first second, then every other second
+    assert_eq!(acted, 6);
+}
+
+#[test]
+fn events_reach_log_and_subscribers() {
+    let (mut u, out) = headless_game(3);
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let s = seen.clone();
+    u.events.subscribe(Box::new(move |event| s.borrow_mut().push(event.clone())));
+
+    let medkit = u.inventory_items().into_iter().find(|id| u.inventory_name_for_id(*id) == "Medkit").unwrap();
+    u.use_item_ext(medkit);
+    assert_eq!(seen.borrow()[0], GameEvent::ItemUsed{ who: "Player".to_string(), item: "Medkit".to_string() });
+    assert!(out.messages().contains(&"Player heals 5 damage".to_string()));
+    assert_eq!(u.stats.borrow().items_used, 1);
+
+    let drained = u.drain_events();
+    assert!(drained.contains("\"ItemUsed\""));
+    assert!(drained.contains("\"TimeAdvanced\""));
+    assert_eq!(u.drain_events(), "[]");
+}
+
+#[test]
+fn attack_events_carry_rolls() {
+    let (mut u, out) = headless_game(3);
+    let player = u.ecs_world.query::<&Player>().iter().next().unwrap().0;
+    let dummy = u.ecs_world.spawn(("Dummy".to_string(), CombatStats{hp:100, max_hp:100, defense:0, power:0}));
+    for _ in 0..10 {
+        u.attack(&player, &dummy);
+    }
+    let stats = u.stats.borrow().clone();
+    assert_eq!(stats.attacks, 10);
+    assert_eq!(100 - u.ecs_world.get::<CombatStats>(dummy).unwrap().hp, stats.damage_dealt);
+    assert_eq!(out.messages().iter().filter(|m| m.as_str() == "Player kicked the Dummy").count(), 10);
+
+    //only the player's kills count
+    let thug = u.ecs_world.spawn(("Thug".to_string(), CombatStats{hp:20, max_hp:20, defense:0, power:0}));
+    let mine = u.ecs_world.spawn(("Rat".to_string(), Point{x:0, y:0}, CombatStats{hp:1, max_hp:1, defense:0, power:0}));
+    let theirs = u.ecs_world.spawn(("Rat".to_string(), Point{x:0, y:0}, CombatStats{hp:1, max_hp:1, defense:0, power:0}));
+    while u.ecs_world.get::<CombatStats>(mine).unwrap().hp > 0 {
+        u.attack(&player, &mine);
+    }
+    while u.ecs_world.get::<CombatStats>(theirs).unwrap().hp > 0 {
+        u.attack(&thug, &theirs);
+    }
+    u.end_turn(COST_DEFAULT);
+    assert!(!u.ecs_world.contains(mine) && !u.ecs_world.contains(theirs));
+    assert_eq!(u.stats.borrow().kills, 1);
+}
+
+#[test]
+fn markup_parses_into_spans() {
+    assert_eq!(parse_markup("{rYou are DEAD!"), vec![Span{ text: "You are DEAD!".to_string(), color: Some(Color::Red) }]);
+    //the closing brace only closes its own tag
+    let spans = parse_markup("Test: {gr0} {c1} sum: {g5");
+    assert_eq!(spans.iter().map(|s| s.color).collect::<Vec<_>>(),
+        vec![None, Some(Color::Gray), None, Some(Color::Cyan), None, Some(Color::Green)]);
+    assert_eq!(LogEntry::new(0, "08:00:00".to_string(), "Dealt {r3} damage {not a tag}").plain(), "Dealt 3 damage {not a tag}");
+    assert_eq!(LogEntry::new(0, "08:00:00".to_string(), "{g<b>").html(), "<span style=\"color:rgb(0,255,0)\">&lt;b&gt;</span>");
+    assert_eq!(LogEntry::new(0, "08:00:00".to_string(), "{rhit").ansi(), "\x1b[31mhit\x1b[0m");
+}
+
+#[test]
+fn message_log_is_stamped() {
+    let (mut u, _out) = headless_game(3);
+    u.on_game_start();
+    u.wait(WaitType::Minutes5);
+    let last = u.message_log.borrow().entries().back().unwrap().clone();
+    assert_eq!(last.time, "08:05:00");
+    assert_eq!(last.turn, 300);
+    assert!(u.render_log(LogFormat::Plain).ends_with("[08:05:00] Time: 08:05:00"));
+    let entries : Vec<LogEntry> = serde_json::from_str(&u.log_entries(u.log_len() - 1)).unwrap();
+    assert_eq!(entries, vec![last]);
+}
+
+#[test]
+fn legacy_saves_are_migrated() {
+    let (mut a, _) = headless_game(11);
+    play(&mut a);
+    let mut current = save_without_timestamp(&a);
+    assert_eq!(current["level"], 0);
+    //back then there was only the town
+    current["entities"].as_array_mut().unwrap().retain(|e| e["level"].is_null() || e["level"]["id"] == 0);
+    for e in current["entities"].as_array_mut().unwrap() {
+        e["level"] = serde_json::Value::Null;
+    }
+    current["levels"] = serde_json::json!([current["levels"][0].clone()]);
+    current["levels"][0]["exits"] = serde_json::json!([]);
+    //nothing knew its prefab
+    let mut old = current.clone();
+    for e in old["entities"].as_array_mut().unwrap() {
+        e["spawned_from"] = serde_json::Value::Null;
+    }
+    //what the game used to write
+    let legacy = format!("{} \nmap:{} \nrng:{}", old["entities"], current["levels"][0], current["rng"]);
+
+    //same seed, so that there are no leftover entities
+    let (mut b, _) = headless_game(11);
+    assert!(b.load_save(legacy));
+    //props get theirs back from their glyph, so that NPCs still find the beds
+    assert!(!b.props_list_by_prefab("Bed").is_empty());
+    for e in current["entities"].as_array_mut().unwrap() {
+        if e["name"] != "" {
+            e["spawned_from"] = serde_json::Value::Null;
+        }
+    }
+    assert_eq!(save_without_timestamp(&b), current);
+    assert_eq!(saveload::parse_save(&format!("{} \nmap:{}", current["entities"], current["levels"][0])).unwrap().game_version, "unknown");
+}
+
+#[test]
+fn bad_saves_are_rejected() {
+    assert_eq!(saveload::parse_save("  ").err(), Some(saveload::SaveError::Empty));
+    assert_eq!(saveload::parse_save("[] \nnope").err(), Some(saveload::SaveError::MissingSection("map".to_string())));
+    assert_eq!(saveload::parse_save("{\"format_version\": 99}").err(), Some(saveload::SaveError::TooNew(99)));
+    assert!(match saveload::parse_save("{\"format_version\": 1}") { Err(saveload::SaveError::Malformed(_)) => true, _ => false });
+
+    //the game goes on
+    let (mut u, out) = headless_game(11);
+    let before = save_without_timestamp(&u);
+    assert!(!u.load_save("garbage".to_string()));
+    assert_eq!(save_without_timestamp(&u), before);
+    assert!(out.messages().last().unwrap().starts_with("Could not load the save"));
+}
+
+// debug output of every component the entity has
+macro_rules! components {
+    ($u:expr, $e:expr, $($t:ty),*) => {{
+        let mut v = Vec::new();
+        $( if let Ok(c) = $u.ecs_world.get::<$t>($e) { v.push(format!("{:?}", *c)); } )*
+        v
+    }};
+}
+
+fn dump_entity(u: &Universe, e: Entity) -> Vec<String> {
+    let mut v = components!(u, e, PersistentId, String, Point, Renderable, Player, GameState, Needs, Money, AI, Speed, Vendor, CombatStats, Faction,
+        Item, InBackpack, Consumable, ProvidesHealing, ProvidesFood, ProvidesQuench, Equippable, MeleeBonus,
+        Attributes, NPCName, Conversation, Path, Asleep, DefenseBonus, ToRemove);
+    //Entity ids change on load, so compare the owner's persistent id
+    if let Ok(equipped) = u.ecs_world.get::<Equipped>(e) {
+        let owner = Entity::from_bits(equipped.owner);
+        v.push(format!("Equipped by {:?} in {:?}", *u.ecs_world.get::<PersistentId>(owner).unwrap(), equipped.slot));
+    }
+    v
+}
+
+fn by_persistent_id(u: &Universe) -> Vec<(PersistentId, Entity)> {
+    let mut v : Vec<(PersistentId, Entity)> = u.ecs_world.query::<&PersistentId>().iter().map(|(id, pid)| (*pid, id)).collect();
+    v.sort_by_key(|(pid, _)| pid.0);
+    v
+}
+
+#[test]
+fn save_round_trips_everything() {
+    let (mut a, _) = headless_game(21);
+    play(&mut a);
+    //late enough for the patrons to look for beds
+    a.wait(WaitType::TillDusk);
+    a.wait(WaitType::Minutes30);
+    a.set_player_stats(vec![3, 2, 1, 0, -1, 1]);
+
+    let mut b = Universe::with_output(Box::new(MemoryOutput::new()));
+    assert!(b.load_save(a.save_game()));
+
+    let ids_a = by_persistent_id(&a);
+    let ids_b = by_persistent_id(&b);
+    assert_eq!(ids_a.len(), a.ecs_world.iter().count());
+    assert_eq!(ids_a.len(), ids_b.len());
+    for (ea, eb) in ids_a.iter().zip(ids_b.iter()) {
+        assert_eq!(dump_entity(&a, ea.1), dump_entity(&b, eb.1));
+    }
+    assert!(a.ecs_world.query::<&Path>().iter().count() > 0);
+
+    assert_eq!(a.player_position, b.player_position);
+    assert_eq!(a.seed, b.seed);
+    assert_eq!(a.map.tiles, b.map.tiles);
+    assert_eq!(a.map.revealed_tiles, b.map.revealed_tiles);
+    for idx in 0..a.map.tiles.len() {
+        assert_eq!(a.map.is_tile_blocked(idx), b.map.is_tile_blocked(idx));
+    }
+    assert_eq!(a.fov_data.transparent, b.fov_data.transparent);
+    assert_eq!(a.fov_data.fov, b.fov_data.fov);
+    assert_eq!(a.rng.gen::<u64>(), b.rng.gen::<u64>());
+}
+
+#[test]
+fn load_into_a_different_world_keeps_owners() {
+    let (mut a, _) = headless_game(31);
+    play(&mut a);
+    let save = a.save_game();
+
+    //a different game with more entities, so that the Entity ids can't line up
+    let (mut b, _) = headless_game(32);
+    for _ in 0..5 {
+        b.give_item("Medkit".to_string());
+    }
+    assert!(b.load_save(save.clone()));
+    assert_eq!(b.ecs_world.iter().count(), a.ecs_world.iter().count());
+
+    let thugs : Vec<Entity> = b.ecs_world.query::<&String>().iter().filter(|(_, n)| n.as_str() == "Thug").map(|(id, _)| id).collect();
+    let worn : Vec<Entity> = b.ecs_world.query::<&Equipped>().iter().map(|(_, eq)| Entity::from_bits(eq.owner)).collect();
+    assert!(!worn.is_empty());
+    for owner in worn {
+        assert!(thugs.contains(&owner));
+    }
+
+    //saving again gives the same ids
+    let resave : serde_json::Value = serde_json::from_str(&b.save_game()).unwrap();
+    let first : serde_json::Value = serde_json::from_str(&save).unwrap();
+    let ids = |v: &serde_json::Value| { let mut ids : Vec<u64> = v["entities"].as_array().unwrap().iter().map(|e| e["entity"].as_u64().unwrap()).collect(); ids.sort(); ids };
+    assert_eq!(ids(&resave), ids(&first));
+}
+
+#[test]
+fn save_slots_keep_several_runs() {
+    let storage = MemoryStorage::new();
+    let (mut a, _) = headless_game(41);
+    a.storage = Box::new(storage.clone());
+    a.set_autosave_policy(r#"{"slots": 0}"#.to_string()); //only the slots saved here
+    assert!(a.save_to_slot("start".to_string()));
+    play(&mut a);
+    assert!(a.save_to_slot("later".to_string()));
+    assert!(a.save_to_slot("later".to_string())); //overwrites
+
+    let slots = a.slot_list();
+    assert_eq!(slots.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["start", "later"]);
+    assert_eq!(slots[0].time, "08:00:00");
+    assert!(slots[1].turns > 300);
+    assert_eq!(slots[1].hp, 20);
+    assert_eq!(slots[1].money, 100.0);
+    assert_eq!(slots[1].seed, 41);
+    assert!(!slots[1].location.is_empty());
+
+    //another session, same storage
+    let (mut b, out) = headless_game(42);
+    b.storage = Box::new(storage.clone());
+    assert!(b.load_from_slot("later".to_string()));
+    assert_eq!(save_without_timestamp(&b), save_without_timestamp(&a));
+
+    assert!(b.delete_save_slot("start".to_string()));
+    assert!(!b.delete_save_slot("start".to_string()));
+    assert_eq!(b.slot_list().len(), 1);
+    assert!(!b.load_from_slot("start".to_string()));
+    assert!(out.messages().last().unwrap().contains("There is no save called start"));
+}
+
+#[test]
+fn old_single_save_imports_into_a_slot() {
+    let (mut a, _) = headless_game(43);
+    play(&mut a);
+    let old = a.save_game();
+
+    let storage = MemoryStorage::new();
+    let (mut b, _) = headless_game(44);
+    b.storage = Box::new(storage.clone());
+    assert!(!b.import_save_slot("imported".to_string(), "not a save".to_string()));
+    assert!(b.slot_list().is_empty());
+    assert!(b.import_save_slot("imported".to_string(), old));
+
+    let slots = b.slot_list();
+    assert_eq!(slots.len(), 1);
+    assert_eq!(slots[0].name, "imported");
+    assert_eq!(slots[0].seed, 43);
+    assert_eq!(slots[0].turns, a.get_time());
+    assert_eq!(slots[0].location, "Imported");
+    assert!(b.load_from_slot("imported".to_string()));
+    assert_eq!(save_without_timestamp(&b), save_without_timestamp(&a));
+}
+
+#[test]
+fn compact_saves_are_smaller_and_load_the_same() {
+    let (mut a, _) = headless_game(51);
+    play(&mut a);
+    let json = a.save_game();
+    let compact = a.save_game_compact();
+    log!("{}", &format!("JSON save: {} bytes, compact: {} bytes", json.len(), compact.len()));
+    assert!(compact.len() * 4 < json.len());
+
+    let (mut b, _) = headless_game(52);
+    assert!(b.load_save_compact(compact.clone()));
+    assert_eq!(save_without_timestamp(&b), save_without_timestamp(&a));
+    assert_eq!(a.fov_data.fov, b.fov_data.fov);
+    assert_eq!(a.rng.gen::<u64>(), b.rng.gen::<u64>());
+
+    //broken ones don't touch the game
+    let (mut c, out) = headless_game(53);
+    let before = save_without_timestamp(&c);
+    assert!(!c.load_save_compact(compact[..compact.len() / 2].to_vec()));
+    assert!(!c.load_save_compact(json.into_bytes()));
+    assert_eq!(save_without_timestamp(&c), before);
+    assert!(out.messages().last().unwrap().contains("Could not load the save"));
+
+    //older versions get migrated, like JSON saves
+    let mut old : serde_json::Value = serde_json::from_str(&a.save_game()).unwrap();
+    old["entities"].as_array_mut().unwrap().retain(|e| e["level"].is_null() || e["level"]["id"] == 0);
+    for e in old["entities"].as_array_mut().unwrap() {
+        e.as_object_mut().unwrap().remove("level");
+    }
+    let mut map = old["levels"][0].clone();
+    map["exits"] = serde_json::json!([]);
+    let runs = saveload::rle_encode(&serde_json::from_value::<Vec<u8>>(map["tiles"].take()).unwrap());
+    map.as_object_mut().unwrap().remove("tiles");
+    map["tiles_rle"] = serde_json::json!(runs);
+    old.as_object_mut().unwrap().remove("levels");
+    old.as_object_mut().unwrap().remove("level");
+    old["map"] = map;
+    old["format_version"] = serde_json::json!(3);
+    let mut bytes = saveload::COMPACT_MAGIC.to_vec();
+    bytes.extend(rmp_serde::to_vec_named(&old).unwrap());
+    let save = saveload::parse_compact(&bytes).unwrap();
+    assert_eq!(save.format_version, saveload::FORMAT_VERSION);
+    assert_eq!((save.levels.len(), save.level), (1, 0));
+
+    //slots can hold either
+    let storage = MemoryStorage::new();
+    a.storage = Box::new(storage.clone());
+    a.set_save_format(saveload::SaveFormat::Compact);
+    assert!(a.save_to_slot("small".to_string()));
+    assert!(storage.get("neon-twilight-slot:small").unwrap().starts_with("b64:"));
+    c.storage = Box::new(storage.clone());
+    assert!(c.load_from_slot("small".to_string()));
+    assert_eq!(save_without_timestamp(&c), save_without_timestamp(&a));
+}
+
+#[test]
+fn rle_round_trips() {
+    let data = vec![1u8, 1, 1, 2, 2, 1, 3, 3, 3, 3];
+    let runs = saveload::rle_encode(&data);
+    assert_eq!(runs, vec![(1, 3), (2, 2), (1, 1), (3, 4)]);
+    assert_eq!(saveload::rle_decode(&runs, data.len()).unwrap(), data);
+    assert!(saveload::rle_encode::<bool>(&[]).is_empty());
+    //counts that don't add up to the map size are rejected before anything gets allocated
+    assert!(saveload::rle_decode(&runs, data.len() + 1).is_err());
+    assert!(saveload::rle_decode(&[(1u8, u32::MAX), (2u8, u32::MAX)], 100).is_err());
+}
+
+#[test]
+fn autosaves_rotate_through_slots() {
+    let (mut u, _) = headless_game(61);
+    let storage = MemoryStorage::new();
+    u.storage = Box::new(storage.clone());
+    assert!(u.set_autosave_policy(r#"{"every_turns": 100, "slots": 2}"#.to_string()));
+    assert!(u.autosave.borrow().policy.before_long_waits); //not given, so default
+
+    play(&mut u);
+    let names : Vec<String> = u.slot_list().iter().map(|s| s.name.clone()).collect();
+    assert_eq!(names.len(), 2);
+    assert!(names.contains(&"autosave-1".to_string()));
+    assert!(names.contains(&"autosave-2".to_string()));
+
+    //the save before resting has the time before it
+    u.set_autosave_policy(r#"{"every_turns": 0, "on_new_area": false, "slots": 1}"#.to_string());
+    let turns = u.get_time();
+    u.rest();
+    let slot = u.slot_list().into_iter().find(|s| s.name == "autosave-1").unwrap();
+    assert_eq!(slot.turns, turns);
+    assert!(u.get_time() > turns);
+
+    //walking into a building isn't a new area, taking the stairs is
+    u.set_autosave_policy(r#"{"every_turns": 0, "before_long_waits": false, "slots": 1}"#.to_string());
+    u.reset_autosave();
+    let indoor = (0..u.map.tiles.len()).position(|idx| u.map.is_tile(idx, "floor")).unwrap();
+    u.player_position = indoor;
+    u.calendar_time();
+    let slot = u.slot_list().into_iter().find(|s| s.name == "autosave-1").unwrap();
+    assert_eq!(slot.turns, turns);
+    let exit = u.map.exits[0];
+    u.change_level(exit);
+    u.calendar_time();
+    let slot = u.slot_list().into_iter().find(|s| s.name == "autosave-1").unwrap();
+    assert_ne!(slot.turns, turns);
+    assert!(slot.location.to_lowercase() == u.level_name(exit.level));
+
+    assert!(!u.set_autosave_policy("nope".to_string()));
+}
+
+#[test]
+fn prefabs_spawn_by_name() {
+    let data = test_data();
+    let (mut u, _) = headless_game(71);
+    for name in data.prefab_names() {
+        assert!(u.spawn(10, 10, name.clone(), &data).is_some(), "{}", name);
+    }
+    assert!(u.spawn(10, 10, "Unicorn".to_string(), &data).is_none());
+
+    //the thug comes dressed
+    let thug = u.spawn(12, 12, "Thug".to_string(), &data).unwrap();
+    let mut worn : Vec<String> = u.ecs_world.query::<(&Equipped, &String)>().iter()
+        .filter(|(_, (eq, _))| eq.owner == thug.to_bits())
+        .map(|(_, (_, name))| name.clone()).collect();
+    worn.sort();
+    assert_eq!(worn, vec!["Boots", "Jeans", "Leather jacket"]);
+    assert!(u.ecs_world.get::<Speed>(thug).is_ok());
+
+    let knife = u.spawn(12, 12, "Combat knife".to_string(), &data).unwrap();
+    assert_eq!(u.ecs_world.get::<MeleeBonus>(knife).unwrap().bonus, 2);
+    //props have no names
+    let table = u.spawn(12, 12, "Table".to_string(), &data).unwrap();
+    assert!(u.ecs_world.get::<String>(table).is_err());
+
+    //the console takes names with spaces
+    let before = u.ecs_world.query::<&String>().iter().filter(|(_, n)| n.as_str() == "Leather jacket").count();
+    u.console_input("spawn Leather jacket".to_string());
+    let after = u.ecs_world.query::<&String>().iter().filter(|(_, n)| n.as_str() == "Leather jacket").count();
+    assert_eq!(after, before + 1);
+}
+
+#[test]
+fn prefabs_cover_every_component() {
+    let mut data = test_data();
+    let (mut u, _) = headless_game(72);
+
+    let barkeep = u.spawn(10, 10, "Barkeep".to_string(), &data).unwrap();
+    assert!(u.ecs_world.get::<Vendor>(barkeep).is_ok());
+    assert_eq!(u.ecs_world.get::<Money>(barkeep).unwrap().money, 500.0);
+    let patron = u.spawn(11, 10, "Patron".to_string(), &data).unwrap();
+    assert_eq!(u.ecs_world.get::<Conversation>(patron).unwrap().text, "Hola, tio!");
+    assert!(!u.ecs_world.get::<NPCName>(patron).unwrap().name.is_empty());
+    let shake = u.spawn(12, 10, "Protein shake".to_string(), &data).unwrap();
+    assert!(u.ecs_world.get::<ProvidesFood>(shake).is_ok());
+    assert!(u.ecs_world.get::<ProvidesQuench>(shake).is_ok());
+    assert!(u.ecs_world.get::<Consumable>(shake).is_ok());
+
+    //a new NPC needs nothing but data
+    let extra : data_loader::NPCPrefab = ron::from_str(r#"#![enable(implicit_some)]
+    (
+        name: "Bouncer", renderable: "Thug", faction: (typ: Townsfolk),
+        combat: CombatStats(hp:15, max_hp:15, defense:2, power:2),
+        attributes: (strength:(base:3, bonus:0), dexterity:(base:0, bonus:0), constitution:(base:2, bonus:0),
+            intelligence:(base:0, bonus:0), wisdom:(base:0, bonus:0), charisma:(base:-1, bonus:0)),
+        money: Money(money:20.0),
+        equipment: ["Combat knife"],
+    )"#).unwrap();
+    data.npcs.push(extra);
+    let bouncer = u.spawn(13, 10, "Bouncer".to_string(), &data).unwrap();
+    assert_eq!(u.ecs_world.get::<Attributes>(bouncer).unwrap().strength.base, 3);
+    assert_eq!(u.ecs_world.get::<Money>(bouncer).unwrap().money, 20.0);
+    assert!(u.ecs_world.query::<&Equipped>().iter().any(|(_, eq)| eq.owner == bouncer.to_bits()));
+    //doesn't move, so the tile is blocked
+    assert!(u.map.is_tile_blocked(u.map.xy_idx(13, 10)));
+}
+
+#[test]
+fn prefabs_inherit_from_parents() {
+    let data = test_data();
+    let jeans = data.find_item("Jeans").unwrap();
+    assert!(jeans.item.is_some()); //from the template
+    assert_eq!(jeans.defense.unwrap().bonus, 0.1); //its own
+    assert_eq!(data.find_item("Boots").unwrap().defense.unwrap().bonus, 0.15);
+    assert!(data.find_item("Medkit").unwrap().renderable.is_some());
+    //templates can't be spawned
+    assert!(data.find_item("Clothing").is_none());
+    assert!(!data.prefab_names().contains(&"Townsfolk".to_string()));
+
+    //grandparents, too
+    //(layered over the base file, which has the map and tiles)
+    let base = include_str!("../docs/data.ron");
+    let data = with_base(base, vec![("test.ron".to_string(), r#"#![enable(implicit_some)]
+    ( items: [ (name: "C", parent: "B"), (name: "B", parent: "A", item: Item()), (name: "A", renderable: "Boots", melee: MeleeBonus(bonus:1)) ] )"#.to_string())]).unwrap();
+    let c = data.find_item("C").unwrap();
+    assert!(c.item.is_some());
+    assert_eq!(c.melee.unwrap().bonus, 1);
+
+    let with_items = |items: &str| with_base(base, vec![("test.ron".to_string(), format!(r#"#![enable(implicit_some)]
+    ( items: [ {} ] )"#, items))]).err().unwrap().error;
+    assert_eq!(with_items(r#"(name: "A", parent: "Nope", renderable: "Boots")"#),
+        data_loader::DataError::MissingParent{ prefab: "A".to_string(), parent: "Nope".to_string() });
+    assert_eq!(with_items(r#"(name: "A", parent: "B"), (name: "B", parent: "A")"#),
+        data_loader::DataError::ParentCycle(vec!["A".to_string(), "B".to_string(), "A".to_string()]));
+    assert_eq!(with_items(r#"(name: "A", parent: "A")"#).to_string(), "prefabs inherit from each other in a loop: A -> A");
+    assert_eq!(with_items(r#"(name: "A")"#),
+        data_loader::DataError::MissingField{ prefab: "A".to_string(), field: "renderable".to_string() });
+}
+
+#[test]
+fn data_errors_say_where() {
+    let source = include_str!("../docs/data.ron");
+    let broken = |from: &str, to: &str| {
+        assert!(source.contains(from));
+        parse_base(&source.replacen(from, to, 1)).err().unwrap()
+    };
+
+    //a typo
+    let e = broken("speed: 100,", "speed: 100,,");
+    let line = source.lines().position(|l| l.contains("speed: 100,")).unwrap() + 1;
+    assert_eq!((e.file.as_str(), e.line), ("data.ron", line));
+    assert!(e.column > 0);
+    assert!(e.to_string().starts_with(&format!("data.ron:{}:", line)));
+
+    //unknown glyph
+    let e = broken("renderable: \"Knife\",", "renderable: \"Spoon\",");
+    assert_eq!(e.error, data_loader::DataError::UnknownGlyph{ prefab: "Combat knife".to_string(), glyph: "Spoon".to_string() });
+    assert!(e.to_string().contains("Spoon"));
+    assert_eq!(e.line, source.lines().position(|l| l.contains("name: \"Combat knife\",")).unwrap() + 1);
+    let e = broken("(id: 10,", "(id: 9,");
+    assert_eq!(e.error, data_loader::DataError::DuplicateGlyph(9));
+
+    //semantic problems
+    let e = broken("name: \"Jeans\",", "name: \"Boots\",");
+    assert_eq!(e.error, data_loader::DataError::DuplicateName("Boots".to_string()));
+    //the definition, not the Thug's equipment or the glyph with the same name
+    assert_eq!(e.line, source.lines().position(|l| l.contains("name: \"Jeans\",")).unwrap() + 1);
+    let e = broken("(renderable: \"Bed\", name: \"Bed\")", "(renderable: \"Cot\", name: \"Bed\")");
+    let bed = source.lines().position(|l| l.contains("name: \"Bed\")")).unwrap();
+    assert_eq!((e.line, e.column), (bed + 1, source.lines().nth(bed).unwrap().find("name:").unwrap() + 7));
+    let e = broken("equipment: [\"Boots\",", "equipment: [\"Sandals\",");
+    assert_eq!(e.error, data_loader::DataError::UnknownPrefab{ referenced_by: "Thug".to_string(), name: "Sandals".to_string() });
+    assert_eq!(e.line, source.lines().position(|l| l.contains("equipment: [\"Boots\",")).unwrap() + 1);
+    let e = broken("(x:6, y:7, name:\"Combat knife\")", "(x:6, y:7, name:\"Spoon\")");
+    assert_eq!(e.error, data_loader::DataError::UnknownPrefab{ referenced_by: "start_spawns".to_string(), name: "Spoon".to_string() });
+
+    //shown in game instead of a panic
+    let out = MemoryOutput::new();
+    let mut u = Universe::with_output(Box::new(out.clone()));
+    u.report_data_error(&e);
+    assert!(u.data_error().contains("Spoon"));
+    assert!(out.messages().last().unwrap().starts_with("Could not load data.ron:"));
+}
+
+#[test]
+fn mods_layer_over_the_base_data() {
+    let base = include_str!("../docs/data.ron");
+    let tougher = ("tougher.ron".to_string(), r#"#![enable(implicit_some)]
+    (
+        npcs: [
+            (name: "Thug", renderable: "Thug", ai: AI(), faction: (typ: Enemy), combat: CombatStats(hp:30, max_hp:30, defense:2, power:3)),
+            (name: "Bartender", parent: "Townsfolk", renderable: "Barkeep"),
+        ],
+        items: [ (name: "Fedora", parent: "Clothing", renderable: "Fedora", equippable: (slot: Torso)) ],
+        glyphs: [ (id: 11, name: "Fedora", glyph: "^", fg: (90, 60, 30)) ],
+        start_spawns: [ (x:7, y:7, name:"Fedora") ],
+    )"#.to_string());
+    let data = with_base(base, vec![tougher.clone()]).unwrap();
+
+    //overridden in place
+    let thug = data.find_npc("Thug").unwrap();
+    assert_eq!(thug.combat.unwrap().hp, 30);
+    assert!(thug.equipment.is_none());
+    assert_eq!(data.npcs.iter().position(|p| p.name == "Thug"), Some(0));
+    assert_eq!(data.source_of("Thug"), Some("tougher.ron"));
+    //appended, with base templates
+    assert_eq!(data.find_item("Fedora").unwrap().defense.unwrap().bonus, 0.15);
+    assert!(data.find_npc("Bartender").unwrap().faction.is_some());
+    assert_eq!(data.source_of("Fedora"), Some("tougher.ron"));
+    assert_eq!(data.source_of("Jeans"), Some("data.ron"));
+    assert_eq!(data.start_spawns.last().unwrap().name, "Fedora");
+    assert_eq!(data.glyph_id("Fedora"), Some(11)); //new looks need no code either
+    assert_eq!(data.glyph_id("Boots"), Some(6));
+    assert_eq!(data.map.width, 80); //only in the base
+
+    //errors point at the mod
+    let broken = ("broken.ron".to_string(), r#"#![enable(implicit_some)]
+    (
+        items: [ (name: "Spoon", parent: "Cutlery", renderable: "Knife") ],
+    )"#.to_string());
+    let e = with_base(base, vec![broken]).err().unwrap();
+    assert_eq!((e.file.as_str(), e.line), ("broken.ron", 3));
+    let e = data_loader::parse_layers(&[tougher]).err().unwrap();
+    assert_eq!(e.error, data_loader::DataError::MissingMap);
+}
+
+#[test]
+fn reloaded_data_updates_spawned_entities() {
+    let (mut u, out) = headless_game(81);
+    let data = test_data();
+    let thug = u.ecs_world.query::<&SpawnedFrom>().iter().find(|(_, s)| s.prefab == "Thug").unwrap().0;
+    u.ecs_world.get_mut::<CombatStats>(thug).unwrap().hp -= 4;
+    let table = u.spawn(3, 3, "Table".to_string(), &data).unwrap();
+    let knife = u.ecs_world.query::<&SpawnedFrom>().iter().find(|(_, s)| s.prefab == "Combat knife").unwrap().0;
+    *u.ecs_world.get_mut::<String>(knife).unwrap() = "Old knife".to_string();
+    //the barkeep's prefab changes, but not the money
+    let barkeep = u.ecs_world.query::<&SpawnedFrom>().iter().find(|(_, s)| s.prefab == "Barkeep").unwrap().0;
+    u.ecs_world.get_mut::<Money>(barkeep).unwrap().money -= 1.0;
+    let till = u.ecs_world.get::<Money>(barkeep).unwrap().money;
+
+    let source = include_str!("../docs/data.ron")
+        .replace("combat: CombatStats(hp:10, max_hp:10, defense:1, power:1),", "combat: CombatStats(hp:20, max_hp:20, defense:1, power:4),")
+        .replace("(renderable: \"Table\", name: \"Table\")", "(renderable: \"Bed\", name: \"Table\")")
+        .replace("melee: MeleeBonus(bonus:2),", "")
+        .replace("combat: CombatStats(hp:5, max_hp:5, defense:1, power:1),\n            vendor", "combat: CombatStats(hp:6, max_hp:6, defense:1, power:1),\n            vendor");
+    let tuned = parse_base(&source).unwrap();
+    *data_loader::PENDING_RELOAD.lock().unwrap() = Some(Ok(tuned));
+    assert!(u.apply_reload());
+    assert!(!u.apply_reload()); //nothing pending anymore
+
+    let combat = *u.ecs_world.get::<CombatStats>(thug).unwrap();
+    assert_eq!((combat.max_hp, combat.hp, combat.power), (20, 16, 4));
+    assert!(u.ecs_world.get::<Speed>(thug).is_ok());
+    assert_eq!(u.ecs_world.get::<Renderable>(table).unwrap().glyph, data.glyph_id("Bed").unwrap());
+    assert!(u.ecs_world.get::<MeleeBonus>(knife).is_err());
+    assert_eq!(*u.ecs_world.get::<String>(knife).unwrap(), "Combat knife");
+    assert_eq!(u.ecs_world.get::<Money>(barkeep).unwrap().money, till);
+    assert_eq!(u.ecs_world.get::<CombatStats>(barkeep).unwrap().max_hp, 6);
+    assert!(out.messages().last().unwrap().starts_with("Data reloaded"));
+
+    *data_loader::PENDING_RELOAD.lock().unwrap() = Some(Err(data_loader::parse_layers(&[("data.ron".to_string(), "(".to_string())]).err().unwrap()));
+    assert!(!u.apply_reload());
+    assert!(out.messages().last().unwrap().starts_with("Could not reload data.ron:"));
+    data_loader::DATA.lock().unwrap().load(test_data());
+}
+
+#[test]
+fn spawn_tables_fill_buildings_and_zones() {
+    use map_builders::BuildingTag;
+    let data = test_data();
+    let mut rng = GameRng::seed_from_u64(5);
+    let mut builder = map_builders::random_builder(data.map.width as i32, data.map.height as i32);
+    builder.build_map(&mut rng, &data).unwrap();
+    let build = &builder.build_data;
+    let spawned_in = |level: usize, tiles: &Vec<usize>, name: &str| build.list_spawns.iter().filter(|s| s.0 == level && s.2 == name && tiles.contains(&s.1)).count();
+
+    let (_, _, pub_area) = build.spawn_areas.iter().find(|a| a.0 == BuildingTag::Pub).unwrap();
+    assert_eq!(spawned_in(0, pub_area, "Barkeep"), 1);
+    assert!(spawned_in(0, pub_area, "Thug") == 0); //only after 22:00
+    for (tag, level, tiles) in build.spawn_areas.iter().filter(|a| a.0 == BuildingTag::Hostel || a.0 == BuildingTag::Upstairs) {
+        assert_eq!(spawned_in(*level, tiles, "Bed"), tiles.len(), "{:?}", tag);
+    }
+    //never two things on one tile
+    let mut tiles : Vec<(usize, usize)> = build.list_spawns.iter().map(|s| (s.0, s.1)).collect();
+    tiles.sort();
+    tiles.dedup();
+    assert_eq!(tiles.len(), build.list_spawns.len());
+
+    let night = data_loader::SpawnEntry{ name: "Thug".to_string(), weight: 1, hours: Some((22, 4)) };
+    assert!(night.active_at(23) && night.active_at(0) && night.active_at(3));
+    assert!(!night.active_at(4) && !night.active_at(12));
+
+    let base = include_str!("../docs/data.ron");
+    let table = |t: &str| ("tables.ron".to_string(), format!("(spawn_tables: [{}])", t));
+    let e = with_base(base, vec![table(r#"(key: Zone(Streets), min: 3, max: 1, entries: [(name: "Thug")])"#)]).err().unwrap();
+    assert!(matches!(e.error, data_loader::DataError::BadSpawnTable{..}));
+    let e = with_base(base, vec![table(r#"(key: Building(Pub), max: 1, entries: [(name: "Unicorn")])"#)]).err().unwrap();
+    assert!(matches!(e.error, data_loader::DataError::UnknownPrefab{..}));
+}
+
+#[test]
+fn tiles_come_from_data() {
+    let base = include_str!("../docs/data.ron");
+    //replaces the water by name, and adds a tile no code knows about
+    let wading = ("wading.ron".to_string(), r#"(
+        tiles: [
+            (id: 7, name: "water", walkable: true, cost: 2.0, glyph: "~", fg: (0, 0, 255)),
+            (id: 11, name: "rubble", walkable: true, cost: 3.0, glyph: ":", fg: (120, 120, 120)),
+        ],
+    )"#.to_string());
+    let data = with_base(base, vec![wading]).unwrap();
+    let mut map = Map::new(5, 5);
+    map.set_tile_defs(&data.tiles);
+    for idx in 0..map.tiles.len() {
+        map.tiles[idx] = map.tile("pavement").unwrap();
+    }
+    map.tiles[6] = map.tile("water").unwrap(); //1,1
+    map.tiles[7] = map.tile("wall").unwrap(); //2,1
+    map.tiles[8] = 11; //3,1
+    assert!(map.is_tile_walkable(1, 1));
+    assert_eq!(map.move_cost(map.xy_idx(1, 1)), 2.0);
+    assert!(!map.is_tile_walkable(2, 1));
+    assert!(!map.is_tile_transparent(map.xy_idx(2, 1)));
+    assert!(map.is_tile_transparent(map.xy_idx(1, 1)));
+    assert_eq!(map.move_cost(map.xy_idx(3, 1)), 3.0);
+    assert!(map.is_tile(8, "rubble"));
+    //the rest stay as the base file has them
+    assert_eq!(data.tiles.len(), test_data().tiles.len() + 1);
+    assert!(map.is_tile_walkable(3, 3));
+
+    //without definitions nothing is walkable
+    assert!(!Map::new(5, 5).is_tile_walkable(3, 3));
+
+    let source = include_str!("../docs/data.ron");
+    let no_doors = source.lines().filter(|l| !l.contains("name: \"door\"")).collect::<Vec<_>>().join("\n");
+    let e = parse_base(&no_doors).err().unwrap();
+    assert_eq!(e.error, data_loader::DataError::MissingTile("door".to_string()));
+    let tile = |t: &str| ("tiles.ron".to_string(), format!("(tiles: [{}])", t));
+    let e = with_base(base, vec![tile(r#"(id: 12, name: "ice", walkable: true, cost: 0.0, glyph: "_", fg: (255, 255, 255))"#)]).err().unwrap();
+    assert!(matches!(e.error, data_loader::DataError::BadTile{..}));
+    assert_eq!((e.file.as_str(), e.line), ("tiles.ron", 1));
+    let e = with_base(base, vec![tile(r#"(id: 3, name: "ice", walkable: true, glyph: "_", fg: (255, 255, 255))"#)]).err().unwrap();
+    assert_eq!(e.error, data_loader::DataError::BadTile{ tile: "ice".to_string(), problem: "id 3 is already tree".to_string() });
+
+    //the map builders say so too, instead of panicking
+    assert_eq!(map.tile("lava"), None);
+    let mut no_tunnels = test_data();
+    no_tunnels.tiles.retain(|t| t.name != "tunnel");
+    let out = MemoryOutput::new();
+    let mut u = Universe::with_output(Box::new(out.clone()));
+    u.game_start(&no_tunnels);
+    assert!(u.data_error().contains("no tile called tunnel"));
+}
+
+#[test]
+fn npc_names_come_from_cultures() {
+    let base = include_str!("../docs/data.ron");
+    let nordic = ("nordic.ron".to_string(), r#"#![enable(implicit_some)]
+    (
+        name_cultures: [ (name: "Nordic", female: ["Astrid", "Sigrid"], surnames: ["Berg"], nicknames: ["Fox"], nickname_chance: 1.0) ],
+        npcs: [ (name: "Shieldmaiden", parent: "Townsfolk", renderable: "Patron", names: (culture: "Nordic", male: 0.0)) ],
+    )"#.to_string());
+    let data = with_base(base, vec![nordic]).unwrap();
+    let (mut u, _out) = headless_game(23);
+    for i in 0..5 {
+        let e = u.spawn(10 + i, 10, "Shieldmaiden".to_string(), &data).unwrap();
+        let name = u.ecs_world.get::<NPCName>(e).unwrap().name.clone();
+        assert!(name == "Astrid \"Fox\" Berg" || name == "Sigrid \"Fox\" Berg", "{}", name);
+    }
+    //the base game's list
+    let patron = u.spawn(10, 12, "Patron".to_string(), &data).unwrap();
+    let name = u.ecs_world.get::<NPCName>(patron).unwrap().name.clone();
+    let spanish = data.find_culture("Spanish").unwrap();
+    assert!(spanish.surnames.iter().any(|s| name.ends_with(s.as_str())), "{}", name);
+
+    let npc = |names: &str| with_base(base, vec![("bad.ron".to_string(),
+        format!(r#"#![enable(implicit_some)]
+        (npcs: [ (name: "Stranger", parent: "Townsfolk", renderable: "Patron", names: {}) ])"#, names))]).err().unwrap();
+    let e = npc(r#"(culture: "Martian")"#);
+    assert!(matches!(e.error, data_loader::DataError::BadNames{..}));
+    assert_eq!(e.file, "bad.ron");
+    let e = npc(r#"(culture: "Spanish", male: 1.5)"#);
+    assert!(matches!(e.error, data_loader::DataError::BadNames{..}));
+}
+
+#[test]
+fn scenarios_set_up_the_start() {
+    let data = test_data();
+    let start = |scenario: Option<&str>| {
+        let out = MemoryOutput::new();
+        let mut u = Universe::with_output(Box::new(out.clone()));
+        u.set_seed(24);
+        if let Some(name) = scenario {
+            u.set_scenario(name.to_string());
+        }
+        u.game_start(&data);
+        (u, out)
+    };
+    let player = |u: &Universe| u.ecs_world.query::<&Player>().iter().next().unwrap().0;
+    let backpack = |u: &Universe| {
+        let mut names : Vec<String> = u.ecs_world.query::<(&String, &InBackpack)>().iter().map(|(_, (n, _))| n.clone()).collect();
+        names.sort();
+        names
+    };
+
+    //the first one is the default
+    let (u, _) = start(None);
+    assert_eq!(u.ecs_world.get::<Money>(player(&u)).unwrap().money, 100.0);
+    assert_eq!(backpack(&u), vec!["Medkit", "Protein shake"]);
+    assert_eq!(u.get_clock().format("%H:%M").to_string(), "08:00");
+    assert_eq!(u.journal.scenario, "Regular");
+
+    let (mut u, _) = start(Some("Night owl"));
+    let e = player(&u);
+    assert_eq!(u.ecs_world.get::<Money>(e).unwrap().money, 20.0);
+    assert_eq!(u.ecs_world.get::<CombatStats>(e).unwrap().max_hp, 15);
+    assert_eq!(u.ecs_world.get::<Attributes>(e).unwrap().dexterity.base, 2);
+    assert_eq!(backpack(&u), vec!["Combat knife"]);
+    assert_eq!(u.get_clock().format("%H:%M").to_string(), "21:30");
+    assert!(u.map.is_tile(u.player_position, "floor"));
+    //the clock runs from the start time, past midnight
+    u.pass_time(3 * 3600);
+    assert_eq!(u.get_clock().format("%H:%M").to_string(), "00:30");
+    //and survives saving
+    let save = u.save_game();
+    let (mut other, _) = start(None);
+    assert!(other.load_save(save));
+    assert_eq!(other.get_clock().format("%H:%M").to_string(), "00:30");
+
+    //a journal only replays on the scenario it was recorded in
+    let journal = u.journal.clone();
+    let (mut regular, _) = start(None);
+    assert!(!regular.replay(&journal));
+
+    //unknown names fall back to the first one, and the player is told
+    let (u, out) = start(Some("Astronaut"));
+    assert_eq!(u.journal.scenario, "Regular");
+    assert_eq!(u.scenario(), "Regular");
+    assert!(out.messages().iter().any(|m| m == "No scenario called Astronaut, starting Regular instead"));
+
+    let source = include_str!("../docs/data.ron");
+    let e = parse_base(&source.replace("inventory: [\"Combat knife\"]", "inventory: [\"Lightsaber\"]")).err().unwrap();
+    assert!(matches!(e.error, data_loader::DataError::UnknownPrefab{..}));
+    let e = parse_base(&source.replace("start_time: (21, 30)", "start_time: (25, 0)")).err().unwrap();
+    assert!(matches!(e.error, data_loader::DataError::BadScenario{..}));
+    assert_eq!(e.line, source.lines().position(|l| l.contains("name: \"Night owl\"")).unwrap() + 1);
+}
+
+#[test]
+fn levels_are_linked_by_stairs() {
+    let (mut u, out) = headless_game(7);
+    //the town, the pub cellar, the hostel's upper floor and the sewers
+    assert_eq!(u.level_count(), 4);
+    let exit = u.map.exits.iter().find(|x| u.level_map(x.level).name == "pub cellar").cloned().unwrap();
+    assert!(u.map.is_tile(exit.idx, "stairs down"));
+    assert!(u.level_map(exit.level).is_tile(exit.dest, "stairs up"));
+
+    //walk onto the stairs from next to them
+    let (sx, sy) = u.map.idx_xy(exit.idx);
+    let (dx, dy) = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().cloned()
+        .find(|d| u.map.is_tile_walkable(sx + d.0, sy + d.1) && !u.map.is_tile_blocked(u.map.xy_idx(sx + d.0, sy + d.1))).unwrap();
+    u.map.clear_tile_blocked(u.player_position);
+    u.player_position = u.map.xy_idx(sx + dx, sy + dy);
+    u.rebuild_blocked();
+    let town : Vec<(Entity, Point)> = u.ecs_world.query::<(&Point, &AI, Option<&Level>)>().iter()
+        .filter(|(_, (_, _, lvl))| on_level(*lvl, 0)).map(|(id, (p, _, _))| (id, *p)).collect();
+    u.move_player(-dx, -dy);
+    assert_eq!(u.level, exit.level);
+    assert_eq!(u.player_position, exit.dest);
+    assert!(out.messages().iter().any(|m| m.contains("You take the stairs to the pub cellar")));
+    assert!(out.description().starts_with("You are in the pub cellar."));
+
+    //only what's down here shows up
+    let data = test_data();
+    let (px, py) = u.map.idx_xy(u.player_position);
+    let shake = u.spawn(px, py, "Protein shake".to_string(), &data).unwrap();
+    assert_eq!(u.entity_level(shake), exit.level);
+    assert_eq!(u.items_at(px as usize, py as usize), Some(shake));
+    for (id, _) in town.iter() {
+        let p = *u.ecs_world.get::<Point>(*id).unwrap();
+        assert!(u.entities_at(p.x as usize, p.y as usize) != Some(*id));
+    }
+
+    //the town goes on without the player
+    u.wait(WaitType::Hour1);
+    assert!(town.iter().any(|(id, p)| *u.ecs_world.get::<Point>(*id).unwrap() != *p));
+
+    //saves keep every level, and who is on which
+    let check = |other: &Universe| {
+        assert_eq!(other.level, u.level);
+        assert_eq!(other.player_position, u.player_position);
+        assert_eq!(other.map.name, "pub cellar");
+        assert_eq!(other.level_map(0).exits, u.level_map(0).exits);
+        assert!(other.items_at(px as usize, py as usize).is_some());
+    };
+    let mut json = Universe::with_output(Box::new(MemoryOutput::new()));
+    assert!(json.load_save(u.save_game()));
+    check(&json);
+    let mut compact = Universe::with_output(Box::new(MemoryOutput::new()));
+    assert!(compact.load_save_compact(u.save_game_compact()));
+    check(&compact);
+
+    //and back up
+    u.change_level(u.map.exit_at(u.player_position).unwrap());
+    assert_eq!(u.level, 0);
+    assert_eq!(u.player_position, exit.idx);
+    assert_eq!(u.items_at(px as usize, py as usize), None);
+
+    //someone standing at the bottom of the stairs
+    let (mut u, out) = headless_game(7);
+    let dest = u.level_map(exit.level);
+    let (dx, dy) = dest.idx_xy(exit.dest);
+    let thug = u.spawn_on(exit.level, dx, dy, "Thug".to_string(), &data).unwrap();
+    u.rebuild_blocked();
+    assert!(u.change_level(exit));
+    assert_ne!(u.player_position, exit.dest);
+    let (px, py) = u.map.idx_xy(u.player_position);
+    assert!(distance2d_chessboard(px, py, dx, dy) == 1);
+    assert_eq!(*u.ecs_world.get::<Point>(thug).unwrap(), Point{x: dx, y: dy});
+    //and nowhere to go at all
+    let back = u.map.exit_at(exit.dest).unwrap();
+    u.change_level(back);
+    let (tx, ty) = u.level_map(exit.level).idx_xy(exit.dest);
+    for y in ty-1..ty+2 {
+        for x in tx-1..tx+2 {
+            let map = u.level_map_mut(exit.level);
+            let idx = map.xy_idx(x, y);
+            map.set_tile_blocked(idx);
+        }
+    }
+    let before = u.player_position;
+    assert!(!u.change_level(exit));
+    assert_eq!((u.level, u.player_position), (0, before));
+    assert!(out.messages().last().unwrap().contains("can't get through"));
+}
+
+#[test]
+fn enemies_prowl_other_levels() {
+    let (mut u, _) = headless_game(7);
+    let sewers = (0..u.level_count()).find(|&l| u.level_name(l) == "sewers").unwrap();
+    let data = test_data();
+    //somewhere in the tunnels, away from the player
+    let idx = (0..u.level_map(sewers).tiles.len()).find(|&idx| u.level_map(sewers).is_tile(idx, "tunnel") && !u.level_map(sewers).is_tile_blocked(idx)).unwrap();
+    let (x, y) = u.level_map(sewers).idx_xy(idx);
+    let thug = u.spawn_on(sewers, x, y, "Thug".to_string(), &data).unwrap();
+    u.rebuild_blocked();
+    u.wait(WaitType::Minutes5);
+    assert_ne!(*u.ecs_world.get::<Point>(thug).unwrap(), Point{x: x, y: y});
+    assert_eq!(u.entity_level(thug), sewers);
+}
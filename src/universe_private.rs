@@ -1,27 +1,30 @@
 use super::log;
-use super::{game_message, 
-    Universe, 
-    Cell, Renderable, RenderableGlyph, RenderOrder, Rolls,
-    ToRemove,
-    Point, Player, GameState, Needs, Money,
-    Vendor, CombatStats, Conversation, NPCName, Attributes, Attribute,
-    WantsToDropItem, WantsToUseItem,
-    Item, InBackpack, Consumable, ProvidesHealing, ProvidesFood, ProvidesQuench, Equippable, EquipmentSlot, MeleeBonus, DefenseBonus, Equipped};
+use super::{Universe, GameOutput, MemoryStorage, Autosave, PersistentId, LogEntry, MessageLog, GameEvent, EventBus, Statistics, Speed, COST_DEFAULT, GameRng, Journal, JournalEntry, Command, CommandQueue, CommandResult, command_from_name,
+    Renderable, RenderOrder,
+    ToRemove, SpawnedFrom,
+    Point, Player, GameState, Needs, Money, Level, on_level,
+    CombatStats, NPCName,
+    WantsToDropItem, WantsToUseItem, LastHitBy,
+    Item, InBackpack, Consumable, ProvidesHealing, ProvidesFood, ProvidesQuench, Equippable, MeleeBonus, DefenseBonus, Equipped};
+
+use std::rc::Rc;
+use std::cell::RefCell;
 
 //ECS
-//use hecs::World;
 use hecs::Entity;
 
 //RNG
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 
 //time
-use chrono::{NaiveTime, Timelike, Duration};
+use chrono::{NaiveTime, Timelike};
 
-use super::data_loader::{DataMaster, NPCPrefab, DATA};
+use super::data_loader::{DataMaster, DataLoadError, Prefab, NPCPrefab, ItemPrefab, DATA, start_reload};
 use super::map_builders;    
+use super::saveload;
 use super::map::*;
 use super::fov::*;
+use hecs::World;
 use super::astar::a_star_search;
 use super::utils::*;
 use super::ai::*;
@@ -68,13 +71,98 @@ pub fn path_to_target(map: &mut Map, sx: usize, sy: usize, tx: usize, ty: usize)
 
 //Methods not exposed to JS
 impl Universe {
+    // the output decides whether we talk to the browser or not (e.g. native tests)
+    pub fn with_output(output: Box<dyn GameOutput>) -> Universe {
+        let mut state = Universe{
+            map: Map::new(2,2), //dummy
+            player_position: 0, //dummy
+            levels: vec![Map::new(0,0)], //the town's slot, see levels.rs
+            level: 0,
+            fov: FovRecursiveShadowCasting::new(),
+            fov_data: MapData::new(2,2), //dummy
+            ecs_world: World::new(),
+            output: output,
+            seed: 0, //dummy
+            rng: GameRng::seed_from_u64(0), //dummy
+            journal: Journal::new(0), //dummy
+            commands: CommandQueue::new(),
+            command_results: Vec::new(),
+            events: EventBus::new(),
+            stats: Rc::new(RefCell::new(Statistics::default())),
+            message_log: RefCell::new(MessageLog::new()),
+            next_persistent_id: 0,
+            storage: Box::new(MemoryStorage::new()), //new() replaces it with the browser's
+            save_format: saveload::SaveFormat::Json,
+            autosave: Rc::new(RefCell::new(Autosave::default())),
+            data_error: None,
+            scenario: None,
+        };
+        //keep the statistics up to date
+        let stats = state.stats.clone();
+        state.events.subscribe(Box::new(move |event| stats.borrow_mut().record(event)));
+        let autosave = state.autosave.clone();
+        state.events.subscribe(Box::new(move |event| autosave.borrow_mut().on_event(event)));
+        state.set_seed(rand::random());
+    
+        state.player_position = state.map.xy_idx(1,1); //default
+
+        log!("We have a universe");
+
+        // We'll return the state with the short-hand
+        state
+    }
+
+    //string can have markup, see message_log.rs
+    pub fn game_message(&self, string: &str) {
+        let turns = self.get_time();
+        let entry = LogEntry::new(turns, self.get_clock().format("%H:%M:%S").to_string(), string);
+        self.output.message(&entry);
+        self.message_log.borrow_mut().push(entry);
+    }
+
+    pub fn report_data_error(&mut self, e: &DataLoadError) {
+        log!("{}", &format!("{}", e));
+        self.game_message(&format!("{{rCould not load {}", e));
+        self.data_error = Some(e.to_string());
+    }
+
+    //log it and tell everyone who's listening
+    pub fn emit(&self, event: GameEvent) {
+        for m in event.messages() {
+            self.game_message(&m);
+        }
+        self.events.publish(event);
+    }
+
+    pub fn game_describe(&self, string: &str) {
+        self.output.describe(string);
+    }
+
     pub fn game_start(&mut self, data: &DataMaster) {
+        //validated, so there is at least one
+        let scenario = data.find_scenario(self.scenario.as_deref()).unwrap();
+        if let Some(wanted) = self.scenario.clone().filter(|s| *s != scenario.name) {
+            self.game_message(&format!("{{rNo scenario called {}, starting {} instead", wanted, scenario.name));
+        }
+        self.scenario = Some(scenario.name.clone());
+
         let map_size = (data.map.width, data.map.height);
         //mapgen
         let mut builder = map_builders::random_builder(map_size.0 as i32, map_size.1 as i32);
+        builder.build_data.hour = scenario.start_time.0;
+        builder.build_data.start_building = scenario.start_building;
+        //start from the seed so that the same seed always gives the same game
+        self.rng = GameRng::seed_from_u64(self.seed);
         // unfortunately we have to pass DataMaster here as trying to acquire mutex within mapgen results in 'cannot recursively acquire mutex'
-        builder.build_map(&data);
-        self.map = builder.build_data.map.clone();
+        if let Err(e) = builder.build_map(&mut self.rng, &data) {
+            //no map, no game
+            self.report_data_error(&DataLoadError{ file: data.files.join(", "), line: 0, column: 0, error: e });
+            return;
+        }
+        let mut levels = vec![builder.build_data.map.clone()];
+        levels.extend(builder.build_data.levels.iter().cloned());
+        //the player starts in the town
+        self.set_levels(levels, 0);
 
         //spawn player on start
         match builder.build_data.starting_position {
@@ -84,18 +172,7 @@ impl Universe {
             }
         }
 
-        //FOV
-        self.fov_data = MapData::new(map_size.0 as usize, map_size.1 as usize);
-
-        //build FOV cache
-        for (idx, tile) in self.map.tiles.iter().enumerate() {
-            if *tile == Cell::Wall as u8 || *tile == Cell::Mountain as u8 {
-                self.fov_data.set_transparent(self.map.idx_xy(idx).0 as usize, self.map.idx_xy(idx).1 as usize, false);
-            }
-        }
-    
-        self.fov_data.clear_fov(); // compute_fov does not clear the existing fov
-        self.fov.compute_fov(&mut self.fov_data, self.map.idx_xy(self.player_position).0 as usize, self.map.idx_xy(self.player_position).1 as usize, 6, true);
+        self.rebuild_fov();
         //reveal tiles
         for (idx, b) in self.fov_data.fov.iter().enumerate() {
             if *b {
@@ -104,111 +181,188 @@ impl Universe {
         }
         
         //rendering and position handled otherwise, so the player Entity only needs combat stats
-        //NOTE: player is always entity id 0
-        // 15, 14, 13, 12, 10, 8 aka elite array
-        let player = self.ecs_world.spawn(("Player".to_string(), Player{}, GameState{turns:0}, CombatStats{hp:20, max_hp: 20, defense:1, power:1}, Money{money:100.0}, Needs{hunger:500, thirst:300}, 
-        Attributes{strength:Attribute{base:2, bonus:0}, dexterity:Attribute{base:1, bonus:0}, constitution:Attribute{base:2, bonus:0}, intelligence:Attribute{base:1,bonus:0}, wisdom:Attribute{base:-1,bonus:0}, charisma:Attribute{base:0,bonus:0}}));
+        let player = self.ecs_world.spawn(("Player".to_string(), Player{}, GameState{turns:0, seed:self.seed, start_time: scenario.start_seconds()},
+        scenario.combat, Money{money:scenario.money}, scenario.needs, Speed{speed:100, energy:0}, scenario.attributes));
         //starting inventory
-        self.give_item("Protein shake".to_string());
-        self.give_item("Medkit".to_string());
+        for name in scenario.inventory.iter() {
+            self.give_item_from(name, data);
+        }
 
         //spawn anything listed
         self.spawn_entities_list(builder.build_data.list_spawns, &data);
         self.spawn_entities(&data);
+        self.rebuild_blocked();
+        self.assign_persistent_ids();
+
+        self.reset_autosave();
+
+        //start recording here, everything above comes from the seed
+        self.journal = Journal::new(self.seed);
+        self.journal.scenario = scenario.name.clone();
+    }
+
+    // FOV cache depends on the map only, so it doesn't need saving
+    pub fn rebuild_fov(&mut self) {
+        self.fov_data = MapData::new(self.map.width as usize, self.map.height as usize);
+
+        for idx in 0..self.map.tiles.len() {
+            if !self.map.is_tile_transparent(idx) {
+                self.fov_data.set_transparent(self.map.idx_xy(idx).0 as usize, self.map.idx_xy(idx).1 as usize, false);
+            }
+        }
+
+        self.fov_data.clear_fov(); // compute_fov does not clear the existing fov
+        self.fov.compute_fov(&mut self.fov_data, self.map.idx_xy(self.player_position).0 as usize, self.map.idx_xy(self.player_position).1 as usize, 6, true);
+    }
+
+    // gives every entity that doesn't have one yet a PersistentId
+    pub fn assign_persistent_ids(&mut self) {
+        let missing : Vec<Entity> = self.ecs_world.query::<()>().without::<PersistentId>().iter().map(|(id, _)| id).collect();
+        for id in missing {
+            self.ecs_world.insert_one(id, PersistentId(self.next_persistent_id));
+            self.next_persistent_id += 1;
+        }
+    }
+
+    //anything that can fight blocks pathing, same as blocking_creatures_at
+    pub fn rebuild_blocked(&mut self) {
+        for l in 0..self.level_count() {
+            self.level_map_mut(l).clear_blocked();
+        }
+        self.map.set_tile_blocked(self.player_position);
+        let blockers : Vec<(Point, usize)> = self.ecs_world.query::<(&Point, &CombatStats, Option<&Level>)>().without::<Player>().iter()
+            .map(|(_id, (point, _combat, lvl))| (*point, lvl.map_or(0, |l| l.id))).collect();
+        for (point, level) in blockers {
+            let map = self.level_map_mut(level);
+            let idx = map.xy_idx(point.x, point.y);
+            map.set_tile_blocked(idx);
+        }
+    }
+
+    //false (and a message) if the save can't be loaded, the current game goes on then
+    pub fn finish_load(&mut self, res: Result<(), saveload::SaveError>) -> bool {
+        match res {
+            Ok(_) => {},
+            Err(e) => {
+                self.game_message(&format!("{{rCould not load the save: {}", e));
+                return false;
+            }
+        }
+        //not saved, since they follow from the map and entities
+        self.set_tile_defs(&DATA.lock().unwrap().tiles);
+        self.rebuild_fov();
+        self.rebuild_blocked();
+        self.reset_autosave();
+        return true;
     }
 
     //moved spawn because of //https://github.com/rustwasm/wasm-bindgen/issues/111 preventing using vec<NPCPrefab> as parameter, too :(
 
-    //TODO: unhardcode order?
-    pub fn spawn(&mut self, x:i32, y:i32, name:String, data: &DataMaster) {
-        //TODO: should be a dict lookup
-        // props
-        if name == "Table".to_string() {
-            self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph: RenderableGlyph::Table as u8, order: RenderOrder::Item}));
-        } else if name == "Chair".to_string() {
-            self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph: RenderableGlyph::Chair as u8, order: RenderOrder::Item}));
-        }
-        else if name == "Bed".to_string() {
-            self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph: RenderableGlyph::Bed as u8, order: RenderOrder::Item}));
-        }
-        //NPCs
-        else if name == "Barkeep".to_string() {
-            self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph:data.npcs[1].renderable as u8, order: RenderOrder::Actor}, data.npcs[1].name.to_string(), data.npcs[1].faction.unwrap(), data.npcs[1].combat.unwrap(), Vendor{}));
-            //doesn't move, so mark his tile as blocked
-            self.map.set_tile_blocked(self.map.xy_idx(x,y));
-            
-            //self.ecs_world.spawn((Point{x:x, y:y}, Renderable::Barkeep as u8, "Barkeep".to_string(), Faction{typ: FactionType::Townsfolk}, CombatStats{hp:5, max_hp:5, defense:1, power:1}, Vendor{}));
-        } 
-        else if name == "Patron".to_string() {
-            let pat = self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph:data.npcs[2].renderable as u8, order: RenderOrder::Actor}, data.npcs[2].name.to_string(), data.npcs[2].ai.unwrap(), data.npcs[2].faction.unwrap(), data.npcs[2].combat.unwrap()));
-            //let pat = self.ecs_world.spawn((Point{x:x, y:y}, Renderable::Patron as u8, "Patron".to_string(), AI{}, Faction{typ: FactionType::Townsfolk}, CombatStats{hp:3, max_hp:3, defense:1, power:1}));
-            let conv = self.ecs_world.insert_one(pat, Conversation{text:"Hola, tio!".to_string(), answers:vec!["Tambien.".to_string(), "No recuerdo español.".to_string()]});
-            
-            //randomized NPC name
-            let sel_name = randomized_NPC_name(true);
-            let nm = self.ecs_world.insert_one(pat, NPCName{name: sel_name.to_string()});
-        } else if name == "Thug".to_string() {
-            let th = self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph:data.npcs[0].renderable as u8, order: RenderOrder::Actor}, data.npcs[0].name.to_string(), data.npcs[0].ai.unwrap(), data.npcs[0].faction.unwrap(), data.npcs[0].combat.unwrap()));
-            //let th = self.ecs_world.spawn((Point{x:x, y:y}, Renderable::Thug as u8, "Thug".to_string(), AI{}, Faction{typ: FactionType::Enemy}, CombatStats{hp:10, max_hp:10, defense:1, power:1}));
-            //their starting equipment
-            let boots = self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph:data.items[0].renderable as u8, order: RenderOrder::Item}, data.items[0].name.to_string(), data.items[0].item.unwrap(), data.items[0].equippable.unwrap(), data.items[0].defense.unwrap(), ToRemove{yes:false}));
-            //let boots = self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph:RenderableGlyph::Boots as u8, order: RenderOrder::Item}, "Boots".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Feet }, DefenseBonus{ bonus: 0.15 }, ToRemove{yes:false}));
-            let l_jacket = self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph:data.items[1].renderable as u8, order: RenderOrder::Item}, data.items[1].name.to_string(), data.items[1].item.unwrap(), data.items[1].equippable.unwrap(), data.items[1].defense.unwrap(), ToRemove{yes:false}));
-            //let l_jacket = self.ecs_world.spawn((Point{x:x,y:y}, Renderable{glyph: RenderableGlyph::Jacket as u8, order: RenderOrder::Item}, "Leather jacket".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Torso }, DefenseBonus{ bonus: 0.15 }, ToRemove{yes:false}));
-            let jeans = self.ecs_world.spawn((Point{x:x, y:y}, Renderable{glyph:data.items[2].renderable as u8, order: RenderOrder::Item}, data.items[2].name.to_string(), data.items[2].item.unwrap(), data.items[2].equippable.unwrap(), data.items[2].defense.unwrap(), ToRemove{yes:false}));
-            //let jeans = self.ecs_world.spawn((Point{x:x,y:y}, Renderable{glyph: RenderableGlyph::Jeans as u8, order: RenderOrder::Item}, "Jeans".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Legs}, DefenseBonus{ bonus:0.1}, ToRemove{yes:false}));
-            self.ecs_world.insert_one(boots, Equipped{ owner: th.to_bits(), slot: EquipmentSlot::Feet});
-            self.ecs_world.insert_one(l_jacket, Equipped{ owner: th.to_bits(), slot: EquipmentSlot::Torso});
-            self.ecs_world.insert_one(jeans, Equipped{ owner: th.to_bits(), slot: EquipmentSlot::Legs});
+    //on the player's level
+    pub fn spawn(&mut self, x:i32, y:i32, name:String, data: &DataMaster) -> Option<Entity> {
+        return self.spawn_on(self.level, x, y, name, data);
+    }
+
+    // looks the name up in props, items and NPCs (in that order)
+    pub fn spawn_on(&mut self, level: usize, x:i32, y:i32, name:String, data: &DataMaster) -> Option<Entity> {
+        if let Some(prop) = data.find_prop(&name) {
+            return Some(self.build_entity(x, y, level, prop, RenderOrder::Item, data));
         }
-        else {
-            log!("Tried to spawn {}", name);
+        if let Some(item) = data.find_item(&name) {
+            return Some(self.spawn_item(x, y, level, item, data));
         }
+        if let Some(npc) = data.find_npc(&name) {
+            return Some(self.spawn_npc(x, y, level, npc, data));
+        }
+        log!("Tried to spawn {}", name);
+        return None;
     }
 
+    // position and looks, the prefab adds the rest
+    pub fn build_entity(&mut self, x:i32, y:i32, level: usize, prefab: &dyn Prefab, order: RenderOrder, data: &DataMaster) -> Entity {
+        let mut builder = hecs::EntityBuilder::new();
+        builder.add(Point{x:x, y:y});
+        builder.add(Level{id: level});
+        //the glyph name was checked when the data loaded
+        builder.add(Renderable{glyph: data.glyph_id(prefab.renderable()).unwrap(), order: order});
+        builder.add(SpawnedFrom{prefab: prefab.name().to_string()});
+        prefab.add_components(&mut builder);
+        return self.ecs_world.spawn(builder.build());
+    }
 
-    pub fn spawn_entities(&mut self, data: &DataMaster) {
-        //spawn entities
-        let th = self.ecs_world.spawn((Point{x:5,y:5}, Renderable{glyph:data.npcs[0].renderable as u8, order: RenderOrder::Actor}, data.npcs[0].name.to_string(), data.npcs[0].ai.unwrap(), data.npcs[0].faction.unwrap(), data.npcs[0].combat.unwrap()));
+    pub fn spawn_item(&mut self, x:i32, y:i32, level: usize, prefab: &ItemPrefab, data: &DataMaster) -> Entity {
+        return self.build_entity(x, y, level, prefab, RenderOrder::Item, data);
+    }
+
+    pub fn spawn_npc(&mut self, x:i32, y:i32, level: usize, prefab: &NPCPrefab, data: &DataMaster) -> Entity {
+        let npc = self.build_entity(x, y, level, prefab, RenderOrder::Actor, data);
+
+        //the builder doesn't have the RNG
+        if let Some(style) = &prefab.names {
+            //validated, so the culture is there
+            let culture = data.find_culture(&style.culture).unwrap();
+            let male = self.rng.gen_bool(style.male);
+            let sel_name = random_name(&mut self.rng, culture, male);
+            self.ecs_world.insert_one(npc, NPCName{name: sel_name.to_string()});
+        }
+
+        //doesn't move, so mark the tile as blocked
+        if prefab.ai.is_none() {
+            let map = self.level_map_mut(level);
+            let idx = map.xy_idx(x,y);
+            map.set_tile_blocked(idx);
+        }
 
-        //let th = self.ecs_world.spawn((Point{x:4, y:4}, Renderable::Thug as u8, "Thug".to_string(), AI{}, Faction{typ: FactionType::Enemy}, CombatStats{hp:10, max_hp:10, defense:1, power:1}));
         //their starting equipment
-        let boots = self.ecs_world.spawn((Point{x:4, y:4}, Renderable{glyph:data.items[0].renderable as u8, order: RenderOrder::Item}, data.items[0].name.to_string(), data.items[0].item.unwrap(), data.items[0].equippable.unwrap(), data.items[0].defense.unwrap(), ToRemove{yes:false}));
-        //let boots = self.ecs_world.spawn((Point{x:4, y:4}, Renderable{glyph:RenderableGlyph::Boots as u8, order: RenderOrder::Item}, "Boots".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Feet }, DefenseBonus{ bonus: 0.15 }, ToRemove{yes:false}));
-        let l_jacket = self.ecs_world.spawn((Point{x:4, y:4}, Renderable{glyph:data.items[1].renderable as u8, order: RenderOrder::Item}, data.items[1].name.to_string(), data.items[1].item.unwrap(), data.items[1].equippable.unwrap(), data.items[1].defense.unwrap(), ToRemove{yes:false}));
-        //let l_jacket = self.ecs_world.spawn((Point{x:4,y:4}, Renderable{glyph: RenderableGlyph::Jacket as u8, order: RenderOrder::Item}, "Leather jacket".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Torso }, DefenseBonus{ bonus: 0.15 }, ToRemove{yes:false}));
-        let jeans = self.ecs_world.spawn((Point{x:4, y:4}, Renderable{glyph:data.items[2].renderable as u8, order: RenderOrder::Item}, data.items[2].name.to_string(), data.items[2].item.unwrap(), data.items[2].equippable.unwrap(), data.items[2].defense.unwrap(), ToRemove{yes:false}));
-       //let jeans = self.ecs_world.spawn((Point{x:4,y:4}, Renderable{glyph: RenderableGlyph::Jeans as u8, order: RenderOrder::Item}, "Jeans".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Legs}, DefenseBonus{ bonus:0.1}, ToRemove{yes:false}));
-        self.ecs_world.insert_one(boots, Equipped{ owner: th.to_bits(), slot: EquipmentSlot::Feet});
-        self.ecs_world.insert_one(l_jacket, Equipped{ owner: th.to_bits(), slot: EquipmentSlot::Torso});
-        self.ecs_world.insert_one(jeans, Equipped{ owner: th.to_bits(), slot: EquipmentSlot::Legs});
-
-        let it = self.ecs_world.spawn((Point{x:6,y:7}, Renderable{glyph: RenderableGlyph::Knife as u8, order: RenderOrder::Item}, "Combat knife".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Melee }, MeleeBonus{ bonus: 2}, ToRemove{yes:false}));
-        let med = self.ecs_world.spawn((Point{x:5, y:5}, Renderable{glyph: RenderableGlyph::Medkit as u8, order: RenderOrder::Item}, "Medkit".to_string(), Item{}, ToRemove{yes:false}, Consumable{}, ProvidesHealing{heal_amount:5}));
-        
-        let boots = self.ecs_world.spawn((Point{x:6, y:18}, Renderable{glyph:data.items[0].renderable as u8, order: RenderOrder::Item}, data.items[0].name.to_string(), data.items[0].item.unwrap(), data.items[0].equippable.unwrap(), data.items[0].defense.unwrap(), ToRemove{yes:false}));
-        //let boots = self.ecs_world.spawn((Point{x:6, y:18}, Renderable{glyph:RenderableGlyph::Boots as u8, order: RenderOrder::Item}, "Boots".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Feet }, DefenseBonus{ bonus: 0.15 }, ToRemove{yes:false}));
-        let l_jacket = self.ecs_world.spawn((Point{x:6, y:18}, Renderable{glyph:data.items[1].renderable as u8, order: RenderOrder::Item}, data.items[1].name.to_string(), data.items[1].item.unwrap(), data.items[1].equippable.unwrap(), data.items[1].defense.unwrap(), ToRemove{yes:false}));
-        //let l_jacket = self.ecs_world.spawn((Point{x:6,y:18}, Renderable{glyph: RenderableGlyph::Jacket as u8, order: RenderOrder::Item}, "Leather jacket".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Torso }, DefenseBonus{ bonus: 0.15 }, ToRemove{yes:false}));
-        let jeans = self.ecs_world.spawn((Point{x:6, y:18}, Renderable{glyph:data.items[2].renderable as u8, order: RenderOrder::Item}, data.items[2].name.to_string(), data.items[2].item.unwrap(), data.items[2].equippable.unwrap(), data.items[2].defense.unwrap(), ToRemove{yes:false}));
-        //let jeans = self.ecs_world.spawn((Point{x:6,y:18}, Renderable{glyph: RenderableGlyph::Jeans as u8, order: RenderOrder::Item}, "Jeans".to_string(), Item{}, Equippable{ slot: EquipmentSlot::Legs}, DefenseBonus{ bonus:0.1}, ToRemove{yes:false}));
-      
+        for name in prefab.equipment.iter().flatten() {
+            match data.find_item(name) {
+                Some(item) => {
+                    let it = self.spawn_item(x, y, level, item, data);
+                    match item.equippable {
+                        Some(equippable) => { self.ecs_world.insert_one(it, Equipped{ owner: npc.to_bits(), slot: equippable.slot}); },
+                        //InBackpack means the player's backpack, so it just stays on the ground
+                        None => log!("{}", &format!("{} can't equip {}", prefab.name, name)),
+                    }
+                },
+                None => log!("{}", &format!("{} has unknown equipment {}", prefab.name, name)),
+            }
+        }
+        return npc;
+    }
+
+    //puts the item in the player's backpack
+    pub fn give_item_from(&mut self, name: &str, data: &DataMaster) {
+        let current_position = self.map.idx_xy(self.player_position);
+        match data.find_item(name) {
+            Some(prefab) => {
+                let it = self.spawn_item(current_position.0, current_position.1, self.level, prefab, data);
+                self.pickup_item(&it);
+                self.assign_persistent_ids();
+            },
+            None => log!("{}", &format!("No item called {}", name)),
+        }
+    }
+
+    pub fn spawn_entities(&mut self, data: &DataMaster) {
+        for sp in data.start_spawns.iter() {
+            self.spawn(sp.x, sp.y, sp.name.clone(), data);
+        }
         //debug
         log!("Spawned entities!");
     }
 
-    pub fn spawn_entities_list(&mut self, list_spawns:Vec<(usize, String)>, data: &DataMaster) {
-        for entity in list_spawns.iter() {
-            let pos = self.map.idx_xy(entity.0);
-            self.spawn(pos.0, pos.1, entity.1.clone(), &data);
+    pub fn spawn_entities_list(&mut self, list_spawns:Vec<(usize, usize, String)>, data: &DataMaster) {
+        for (level, idx, name) in list_spawns.iter() {
+            let pos = self.level_map(*level).idx_xy(*idx);
+            self.spawn_on(*level, pos.0, pos.1, name.clone(), &data);
         }
     }
 
 
     pub fn blocking_creatures_at(&self, x: usize, y: usize) -> Option<Entity> {
         let mut blocked: Option<Entity> = None;
-        for (id, (point, combat)) in self.ecs_world.query::<(&Point, &CombatStats)>().iter() {
-            if point.x as usize == x && point.y as usize == y {
+        for (id, (point, combat, lvl)) in self.ecs_world.query::<(&Point, &CombatStats, Option<&Level>)>().iter() {
+            if point.x as usize == x && point.y as usize == y && on_level(lvl, self.level) {
                 blocked = Some(id);
                 break;
             }
@@ -218,11 +372,11 @@ impl Universe {
 
     pub fn entities_at(&self, x: usize, y: usize) -> Option<Entity> {
         let mut ent: Option<Entity> = None;
-        for (id, (point, render)) in self.ecs_world.query::<(&Point, &Renderable)>()
+        for (id, (point, render, lvl)) in self.ecs_world.query::<(&Point, &Renderable, Option<&Level>)>()
         .without::<InBackpack>().without::<Equipped>() //no ref/pointer here!
         .with::<String>() //because props don't have names
         .iter() {
-            if point.x as usize == x && point.y as usize == y {
+            if point.x as usize == x && point.y as usize == y && on_level(lvl, self.level) {
                 ent = Some(id);
                 break;
             }
@@ -230,13 +384,13 @@ impl Universe {
         return ent;
     }
 
-    pub fn props_list_by_render(&self, render_f: u8) -> Vec<Entity> {
+    pub fn props_list_by_prefab(&self, prefab: &str) -> Vec<Entity> {
         let mut props = Vec::new();
-        //props do not have a name, just a point and render
-        for (id, (point, render)) in self.ecs_world.query::<(&Point, &Renderable)>()
+        //props do not have a name, just a point, render and the prefab they came from
+        for (id, (point, from)) in self.ecs_world.query::<(&Point, &SpawnedFrom)>()
         .without::<String>()
         .iter() {
-            if render.glyph == render_f {
+            if from.prefab == prefab {
                 props.push(id);
             }
         }
@@ -245,10 +399,10 @@ impl Universe {
 
     pub fn items_at(&self, x: usize, y: usize) -> Option<Entity> {
         let mut item: Option<Entity> = None;
-        for (id, (point, it)) in self.ecs_world.query::<(&Point, &Item)>()
+        for (id, (point, it, lvl)) in self.ecs_world.query::<(&Point, &Item, Option<&Level>)>()
         .without::<InBackpack>().without::<Equipped>() //no ref/pointer here!!!
         .iter() {
-            if point.x as usize == x && point.y as usize == y {
+            if point.x as usize == x && point.y as usize == y && on_level(lvl, self.level) {
                 item = Some(id);
                 break;
             }
@@ -278,7 +432,7 @@ impl Universe {
         self.ecs_world.insert_one(*user, WantsToUseItem{item:*it});
 
         //message
-        game_message(&format!("{} used {}", self.ecs_world.get::<String>(*user).unwrap().to_string(), self.ecs_world.get::<String>(*it).unwrap().to_string()));
+        self.emit(GameEvent::ItemUsed{ who: self.ecs_world.get::<String>(*user).unwrap().to_string(), item: self.ecs_world.get::<String>(*it).unwrap().to_string() });
         // apply the use effects
         let mut wants : Vec<Entity> = Vec::new();
         let mut to_unequip : Vec<Entity> = Vec::new();
@@ -292,16 +446,16 @@ impl Universe {
                 //actually heal!
                 let mut stats = self.ecs_world.get_mut::<CombatStats>(*user).unwrap();
                 stats.hp += self.ecs_world.get::<ProvidesHealing>(wantstouse.item).unwrap().heal_amount;
-                game_message(&format!("{{g{} heals {} damage", self.ecs_world.get::<String>(*user).unwrap().to_string(), self.ecs_world.get::<ProvidesHealing>(wantstouse.item).unwrap().heal_amount));                
+                self.emit(GameEvent::Healed{ who: self.ecs_world.get::<String>(*user).unwrap().to_string(), amount: self.ecs_world.get::<ProvidesHealing>(wantstouse.item).unwrap().heal_amount });
             } else {
                 log!("Item doesn't provide healing");
             }
 
             // food or drink?
             if self.ecs_world.get::<ProvidesQuench>(wantstouse.item).is_ok(){
-                game_message(&format!("{{gYou drink the {}", self.ecs_world.get::<String>(*it).unwrap().to_string()));
+                self.emit(GameEvent::ItemDrunk{ who: self.ecs_world.get::<String>(*user).unwrap().to_string(), item: self.ecs_world.get::<String>(*it).unwrap().to_string() });
             } else if self.ecs_world.get::<ProvidesFood>(wantstouse.item).is_ok(){
-                game_message(&format!("{{gYou eat the {}", self.ecs_world.get::<String>(*it).unwrap().to_string()));
+                self.emit(GameEvent::ItemEaten{ who: self.ecs_world.get::<String>(*user).unwrap().to_string(), item: self.ecs_world.get::<String>(*it).unwrap().to_string() });
             }
 
             // If it is equippable, then we want to equip it - and unequip whatever else was in that slot
@@ -313,7 +467,7 @@ impl Universe {
                     if owner == *user {
                         to_unequip.push(wantstouse.item);
                         //if target == *player_entity {
-                        game_message(&format!("{{rYou unequip {}.", self.ecs_world.get::<String>(wantstouse.item).unwrap().to_string()));
+                        self.emit(GameEvent::ItemUnequipped{ who: self.ecs_world.get::<String>(*user).unwrap().to_string(), item: self.ecs_world.get::<String>(wantstouse.item).unwrap().to_string() });
                     }
                 }
                 else {
@@ -332,11 +486,11 @@ impl Universe {
                         if owner == *user && equipped.slot == target_slot {
                             to_unequip.push(ent_id);
                             //if target == *player_entity {
-                            game_message(&format!("{{rYou unequip {}.", self.ecs_world.get::<String>(ent_id).unwrap().to_string()));
+                            self.emit(GameEvent::ItemUnequipped{ who: self.ecs_world.get::<String>(*user).unwrap().to_string(), item: self.ecs_world.get::<String>(ent_id).unwrap().to_string() });
                         }   
                     }
                     wants.push(wantstouse.item);
-                    game_message(&format!("{{g{} equips {}", self.ecs_world.get::<String>(*user).unwrap().to_string(), self.ecs_world.get::<String>(*it).unwrap().to_string()));
+                    self.emit(GameEvent::ItemEquipped{ who: self.ecs_world.get::<String>(*user).unwrap().to_string(), item: self.ecs_world.get::<String>(*it).unwrap().to_string() });
                 }
                
             }
@@ -372,7 +526,7 @@ impl Universe {
         self.ecs_world.insert_one(*user, WantsToDropItem{item:*it});
 
         //message
-        game_message(&format!("{} drops {}", self.ecs_world.get::<String>(*user).unwrap().to_string(), self.ecs_world.get::<String>(*it).unwrap().to_string()));
+        self.emit(GameEvent::ItemDropped{ who: self.ecs_world.get::<String>(*user).unwrap().to_string(), item: self.ecs_world.get::<String>(*it).unwrap().to_string() });
         //scope to get around borrow checker
         {
             let user_pos = self.map.idx_xy(self.player_position);
@@ -388,27 +542,30 @@ impl Universe {
             }
         }
 
+        //the player might have picked it up on another level
+        self.ecs_world.insert_one(*it, Level{id: self.level});
         self.ecs_world.remove_one::<InBackpack>(*it);
         
     }
 
 
     //a very simple test, akin to flipping a coin or throwing a d2
-    fn make_test_d2(&self, skill: u32) -> Vec<bool> {
+    fn make_test_d2(&mut self, skill: u32) -> Vec<bool> {
         let mut rolls = Vec::new();
         for _ in 0..10-skill { // exclusive of end
-            rolls.push(rand::random()) // generates a boolean
+            rolls.push(self.rng.gen()) // generates a boolean
         }
         return rolls
     }
 
-    pub fn attack(&self, target: &Entity) {
+    pub fn attack(&mut self, attacker: &Entity, target: &Entity) {
         let res = self.make_test_d2(1);
         let sum = res.iter().filter(|&&b| b).count(); //iter returns references and filter works with references too - double indirection
-        game_message(&format!("Test: {} sum: {{g{}", Rolls(res), sum));
+        let attacker_name = self.ecs_world.get::<String>(*attacker).unwrap().to_string();
+        let target_name = self.ecs_world.get::<String>(*target).unwrap().to_string();
+        let by_player = self.ecs_world.get::<Player>(*attacker).is_ok();
 
         if sum >= 5 {
-            game_message(&format!("Attack hits!"));
             //item bonuses
             let mut offensive_bonus = 0;
             for (id, (power_bonus, equipped_by)) in self.ecs_world.query::<(&MeleeBonus, &Equipped)>().iter() {
@@ -418,9 +575,12 @@ impl Universe {
 
             //deal damage
             // the mut here is obligatory!!!
-            let mut stats = self.ecs_world.get_mut::<CombatStats>(*target).unwrap();
-            stats.hp = stats.hp - 2 - offensive_bonus;
-            game_message(&format!("Dealt {{r{}}} damage", 2+offensive_bonus));
+            {
+                let mut stats = self.ecs_world.get_mut::<CombatStats>(*target).unwrap();
+                stats.hp = stats.hp - 2 - offensive_bonus;
+            }
+            self.ecs_world.insert_one(*target, LastHitBy{ player: by_player });
+            self.emit(GameEvent::AttackHit{ attacker: attacker_name, target: target_name, by_player: by_player, rolls: res, damage: 2+offensive_bonus });
             
             //borrow checker doesn't allow this??
             //if killed, despawn
@@ -429,7 +589,7 @@ impl Universe {
             //     log!("{}", &format!("Target was killed!"));
             // }
         } else {
-            game_message(&format!("Attack missed!"));
+            self.emit(GameEvent::AttackMissed{ attacker: attacker_name, target: target_name, by_player: by_player, rolls: res });
         }
     }
 
@@ -453,11 +613,98 @@ impl Universe {
         return dead;
     }
 
-    pub fn end_turn(&mut self) {
+    pub fn process_command(&mut self, cmd: Command) -> CommandResult {
+        if self.is_player_dead() {
+            return CommandResult::Rejected("player is dead".to_string());
+        }
+
+        self.journal.record(JournalEntry::Process(cmd));
+        self.journal.pause();
+        let mut result = CommandResult::Consumed;
+        match cmd {
+            // Cursors
+            Command::MoveUp => self.move_player(0, -1),
+            Command::MoveDown => self.move_player(0, 1),
+            Command::MoveLeft => self.move_player(-1, 0),
+            Command::MoveRight => self.move_player(1, 0),
+
+            //others
+            Command::GetItem => self.get_item(),
+            Command::Rest => self.rest(),
+            //just let a turn pass
+            Command::Wait => self.end_turn(COST_DEFAULT),
+
+            //save/load and inventory are handled on JS side
+            _ => { result = CommandResult::Rejected(format!("{:?} is not a game command", cmd)); }
+        }
+        self.journal.resume();
+        return result;
+    }
+
+    // the player spent `cost` energy on an action, run the world until they can act again
+    pub fn end_turn(&mut self, cost: i32) {
+        //get player entity
+        let mut play: Option<Entity> = None;
+        for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
+            play = Some(id);
+        }
+        match play {
+            Some(entity) => {
+                if self.ecs_world.get::<Speed>(entity).is_ok() {
+                    self.ecs_world.get_mut::<Speed>(entity).unwrap().energy -= cost;
+                }
+                //at least a second passes for every action
+                loop {
+                    self.tick();
+                    self.remove_dead();
+                    self.survival_tick();
+
+                    if self.is_player_dead() {
+                        break;
+                    }
+                    //no Speed means normal speed, aka one action per second
+                    if self.ecs_world.get::<Speed>(entity).is_err() || self.ecs_world.get::<Speed>(entity).unwrap().energy >= 0 {
+                        break;
+                    }
+                }
+                self.calendar_time();
+            },
+            None => {},
+        }
+    }
+
+    // one second (turn) passes: everyone gets energy and the AIs that have enough act
+    fn tick(&mut self) {
+        //get player entity
+        let mut play: Option<Entity> = None;
+        for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
+            play = Some(id);
+        }
+        match play {
+            Some(entity) => {
+                self.ecs_world.get_mut::<GameState>(entity).unwrap().turns += 1;
+            },
+            None => {},
+        }
+
+        for (_id, speed) in &mut self.ecs_world.query::<&mut Speed>() {
+            //cap it, so that waiting doesn't bank turns
+            speed.energy = std::cmp::min(speed.energy + speed.speed, speed.speed);
+        }
+
         self.get_AI();
-        self.remove_dead();
-        self.survival_tick();
-        self.calendar_time();
+    }
+
+    // for waiting and resting, the player doesn't act during this time
+    pub fn pass_time(&mut self, seconds: i64) {
+        for _ in 0..seconds {
+            self.tick();
+            //reenable when it makes sense to do so
+            //no inter-AI combat yet
+            //self.remove_dead();
+            // needs rebalancing for 1s turns
+            //self.survival_tick();
+        }
     }
 
     fn survival_tick(&mut self) {
@@ -476,7 +723,7 @@ impl Universe {
         }
     }
 
-    fn calendar_time(&mut self) {
+    pub fn calendar_time(&self) {
         //get player entity
         let mut play: Option<Entity> = None;
         for (id, (player)) in self.ecs_world.query::<(&Player)>().iter() {
@@ -484,19 +731,17 @@ impl Universe {
         }
         match play {
             Some(entity) => {
-                let mut gs = self.ecs_world.get_mut::<GameState>(entity).unwrap();
-                gs.turns += 1;
-                //t is a tuple (NaiveTime, i64)
-                let t = NaiveTime::from_hms(08, 00, 00).overflowing_add_signed(Duration::seconds(gs.turns));
-                let f = t.0.format("%H:%M:%S").to_string();
-                game_message(&format!("Time: {}", f));
+                let gs = self.ecs_world.get::<GameState>(entity).unwrap();
+                let f = gs.clock().format("%H:%M:%S").to_string();
+                self.emit(GameEvent::TimeAdvanced{ turns: gs.turns, time: f });
             },
             None => {},
         }
+        self.autosave_tick();
 
     }
 
-    fn get_time(&self) -> i64 {
+    pub fn get_time(&self) -> i64 {
         let mut time = 0;
 
         //get player entity
@@ -514,12 +759,14 @@ impl Universe {
         return time;
     }
 
+    //08:00 before there is a player
+    pub fn get_clock(&self) -> NaiveTime {
+        let clock = self.ecs_world.query::<(&Player, &GameState)>().iter().next().map(|(_, (_, gs))| gs.clock());
+        return clock.unwrap_or(NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+    }
+
     pub fn get_time_of_day(&self) -> i64 {
-        let tm = self.get_time();
-        //t is a tuple (NaiveTime, i64)
-        let t = NaiveTime::from_hms(08, 00, 00).overflowing_add_signed(Duration::seconds(tm));
-        let time = t.0.signed_duration_since(NaiveTime::from_hms(00, 00,00));
-        return time.num_seconds();
+        return self.get_clock().num_seconds_from_midnight() as i64;
     }
 
 
@@ -527,15 +774,15 @@ impl Universe {
     fn remove_dead(&mut self) {
         // Here we query entities with 0 or less hp and despawn them
         let mut to_remove: Vec<Entity> = Vec::new();
-        let mut to_drop : Vec<(Entity, Point)> = Vec::new();
+        let mut to_drop : Vec<(Entity, Point, usize)> = Vec::new();
         for (id, stats) in &mut self.ecs_world.query::<&CombatStats>() {
             if stats.hp <= 0 {
-                if id.id() > 0 { 
+                if self.ecs_world.get::<Player>(id).is_err() { 
                     to_remove.push(id);
                 }
                 // player - just a log message
                 else {
-                    game_message("{rYou are DEAD!");
+                    self.emit(GameEvent::PlayerDied);
                 }
             }
         }
@@ -550,6 +797,7 @@ impl Universe {
             // not item
             if self.ecs_world.get::<Item>(entity).is_err() {
                 //drop their stuff
+                let level = self.entity_level(entity);
                 let pos = self.ecs_world.get::<Point>(entity).unwrap();
                 for (ent_id, (equipped)) in self.ecs_world.query::<(&Equipped)>()
                 .with::<String>()
@@ -557,11 +805,12 @@ impl Universe {
                 {
                     let owner = hecs::Entity::from_bits(equipped.owner);
                     if owner == entity {
-                        to_drop.push((ent_id, *pos));
+                        to_drop.push((ent_id, *pos, level));
                     }
                 }
 
-                game_message(&format!("{{grAI {} is dead", self.ecs_world.get::<String>(entity).unwrap().to_string()));
+                let by_player = self.ecs_world.get::<LastHitBy>(entity).map_or(false, |hit| hit.player);
+                self.emit(GameEvent::EntityDied{ name: self.ecs_world.get::<String>(entity).unwrap().to_string(), by_player: by_player });
             }
             
             self.ecs_world.despawn(entity).unwrap();
@@ -570,6 +819,7 @@ impl Universe {
         // deferred some actions because we can't add or remove components when iterating
         for it in to_drop.iter() {
             self.ecs_world.remove_one::<Equipped>(it.0);
+            self.ecs_world.insert_one(it.0, Level{id: it.2});
             let mut pt = self.ecs_world.get_mut::<Point>(it.0).unwrap();
             pt.x = it.1.x;
             pt.y = it.1.y;
@@ -590,7 +840,13 @@ impl Universe {
                     log!("Not enough parameters supplied");
                 } else {
                     let current_position = self.map.idx_xy(self.player_position);
-                    self.spawn(current_position.0+1, current_position.1+1, v[1].to_string(), &DATA.lock().unwrap())
+                    //names can have spaces, e.g. spawn Combat knife
+                    let name = v[1..].join(" ");
+                    let data = DATA.lock().unwrap();
+                    if self.spawn(current_position.0+1, current_position.1+1, name, &data).is_none() {
+                        log!("{}", &format!("Known prefabs: {}", data.prefab_names().join(", ")));
+                    }
+                    self.assign_persistent_ids();
                 }
             },
             "time" => {
@@ -611,6 +867,33 @@ impl Universe {
                     None => {},
                 }
             }
+            "queue" => {
+                //e.g. queue MoveLeft MoveLeft GetItem
+                for name in v.iter().skip(1) {
+                    match command_from_name(name) {
+                        Some(cmd) => self.commands.push(cmd),
+                        None => log!("{}", &format!("Unknown command {}", name)),
+                    }
+                }
+                self.process(None);
+                log!("{}", &self.command_results());
+            }
+            "journal" => {
+                //paste this into a bug report
+                log!("{}", &self.journal.to_json());
+            }
+            //fetches the data files again, the game picks them up on the next tick
+            "reload" => {
+                start_reload();
+            }
+            //which data file a prefab came from
+            "source" => {
+                let name = v[1..].join(" ");
+                match DATA.lock().unwrap().source_of(&name) {
+                    Some(file) => log!("{}", &format!("{} is defined in {}", name, file)),
+                    None => log!("{}", &format!("No prefab called {}", name)),
+                }
+            }
             _ => { log!("Unknown command entered"); }
         }
     }